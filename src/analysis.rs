@@ -0,0 +1,575 @@
+use crate::output::{markdown_code, predicted_time, SplitFile};
+use crate::timings::{get_file_stem, CostModel};
+use serde_derive::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Overview of how the spec files are split, as printed by 'analyze'.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Analysis {
+    pub buckets: Vec<AnalysisBucket>,
+    pub stats: BalanceStats,
+    /// Time attributed to spec files from suite-level timings, ex. before(:all) hooks
+    pub unattributed_time: f64,
+    pub uncovered_count: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AnalysisBucket {
+    pub predicted_time: f64,
+    pub files: Vec<SplitFile>,
+}
+
+/// How good a split is compared to the best one possible.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BalanceStats {
+    pub total_time: f64,
+    /// Time of every bucket if the suite could be split perfectly (total / N)
+    pub ideal_time: f64,
+    /// No split can be faster than its largest file or the ideal time
+    pub lower_bound: f64,
+    /// Time of the slowest bucket
+    pub makespan: f64,
+    /// Ratio of the slowest to the fastest bucket, None if a bucket is empty
+    pub max_min_ratio: Option<f64>,
+    /// Share of the nodes' time spent running specs instead of waiting on the
+    /// slowest node (total / (N × makespan))
+    pub efficiency: f64,
+    /// Spec file taking longer than the ideal time on its own, which makes it
+    /// the lower bound
+    pub bottleneck_file: Option<SplitFile>,
+}
+
+impl Analysis {
+    pub fn new(
+        buckets: Vec<Vec<SplitFile>>,
+        unattributed_time: f64,
+        cost_model: &CostModel,
+    ) -> Analysis {
+        let buckets = buckets
+            .into_iter()
+            .map(|files| AnalysisBucket {
+                predicted_time: predicted_time(&files, cost_model),
+                files,
+            })
+            .collect::<Vec<_>>();
+
+        let largest_file = buckets
+            .iter()
+            .flat_map(|b| b.files.iter())
+            .filter(|(_, t)| t.is_some())
+            // first of the largest files, ties are broken by bucket order
+            .min_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        let largest_file_time = largest_file
+            .map(|file| predicted_time(std::slice::from_ref(file), cost_model))
+            .unwrap_or(0.0);
+
+        let bucket_times = buckets.iter().map(|b| b.predicted_time).collect::<Vec<_>>();
+        let total_time = bucket_times.iter().sum::<f64>();
+        let ideal_time = total_time / bucket_times.len() as f64;
+        let makespan = bucket_times.iter().cloned().fold(0.0, f64::max);
+        let min_time = bucket_times.iter().cloned().fold(f64::INFINITY, f64::min);
+
+        Analysis {
+            stats: BalanceStats {
+                total_time,
+                ideal_time,
+                lower_bound: largest_file_time.max(ideal_time),
+                makespan,
+                max_min_ratio: if min_time > 0.0 {
+                    Some(makespan / min_time)
+                } else {
+                    None
+                },
+                efficiency: if makespan > 0.0 {
+                    total_time / (bucket_times.len() as f64 * makespan)
+                } else {
+                    1.0
+                },
+                bottleneck_file: largest_file
+                    .filter(|_| largest_file_time > ideal_time)
+                    .cloned(),
+            },
+            uncovered_count: buckets
+                .iter()
+                .flat_map(|b| b.files.iter())
+                .filter(|(_, t)| t.is_none())
+                .count(),
+            buckets,
+            unattributed_time,
+        }
+    }
+}
+
+/// Number of files listed per bucket in the Markdown summary.
+const MARKDOWN_TOP_FILES: usize = 3;
+
+impl Analysis {
+    /// Renders the analysis as GitHub-flavored Markdown, ex. for pull request comments
+    /// or `$GITHUB_STEP_SUMMARY`.
+    pub fn markdown(&self) -> String {
+        let stats = &self.stats;
+        let mut markdown = String::new();
+        markdown.push_str("| Bucket | Time | Files | Slowest files |\n");
+        markdown.push_str("| ---: | ---: | ---: | --- |\n");
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            let mut files = bucket
+                .files
+                .iter()
+                .filter_map(|(file_path, time)| time.map(|time| (file_path, time)))
+                .collect::<Vec<_>>();
+            files.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+            let top_files = files
+                .iter()
+                .take(MARKDOWN_TOP_FILES)
+                .map(|(file_path, time)| format!("{} {:.2}s", markdown_code(file_path), time))
+                .collect::<Vec<_>>();
+            markdown.push_str(&format!(
+                "| {} | {:.2}s | {} | {} |\n",
+                index + 1,
+                bucket.predicted_time,
+                bucket.files.len(),
+                top_files.join("<br>")
+            ));
+        }
+
+        markdown.push_str("\n| Total | Ideal per bucket | Lower bound | Slowest bucket | Max/min | Efficiency |\n");
+        markdown.push_str("| ---: | ---: | ---: | ---: | ---: | ---: |\n");
+        markdown.push_str(&format!(
+            "| {:.2}s | {:.2}s | {:.2}s | {:.2}s | {} | {:.1}% |\n",
+            stats.total_time,
+            stats.ideal_time,
+            stats.lower_bound,
+            stats.makespan,
+            match stats.max_min_ratio {
+                Some(ratio) => format!("{:.2}", ratio),
+                None => "n/a (empty bucket)".to_string(),
+            },
+            stats.efficiency * 100.0
+        ));
+
+        if let Some((file_path, _)) = &stats.bottleneck_file {
+            markdown.push_str(&format!(
+                "\n**Bottleneck:** {} takes {:.2}s on its own, more than the ideal {:.2}s.\n",
+                markdown_code(file_path),
+                stats.lower_bound,
+                stats.ideal_time
+            ));
+        }
+
+        if self.uncovered_count > 0 {
+            markdown.push_str(&format!(
+                "\n> [!WARNING]\n> Found {} spec files not covered by the timing information, \
+                 please re-run the split timing script to fix.\n",
+                self.uncovered_count
+            ));
+        }
+        if let Some(empty_buckets) = self.empty_buckets() {
+            markdown.push_str(&format!(
+                "\n> [!WARNING]\n> {} without spec files would run the entire suite, \
+                 use fewer splits or `split --on-empty`.\n",
+                empty_buckets
+            ));
+        }
+        markdown
+    }
+
+    /// Names the buckets without spec files, ex. "BUCKET 3, 4", if there are any.
+    fn empty_buckets(&self) -> Option<String> {
+        let empty_buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, bucket)| bucket.files.is_empty())
+            .map(|(index, _)| (index + 1).to_string())
+            .collect::<Vec<_>>();
+        if empty_buckets.is_empty() {
+            return None;
+        }
+        Some(format!("BUCKET {}", empty_buckets.join(", ")))
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            let file_names = bucket
+                .files
+                .iter()
+                .map(|(file_path, time)| {
+                    let file_stem = get_file_stem(Path::new(file_path));
+                    match time {
+                        Some(time) => format!("{}:{:.2}s", file_stem, time),
+                        None => format!("{}:NA", file_stem),
+                    }
+                })
+                .collect::<Vec<_>>();
+            writeln!(
+                f,
+                "[BUCKET {} - {:.2}s] {}",
+                index + 1,
+                bucket.predicted_time,
+                file_names.join(", ")
+            )?;
+        }
+
+        if self.unattributed_time > 0.0 {
+            writeln!(
+                f,
+                "Unattributed time: {:.2}s ({:.1}% of {:.2}s) isn't part of any example and was attributed from suite-level timings",
+                self.unattributed_time,
+                self.unattributed_time / self.stats.total_time * 100.0,
+                self.stats.total_time
+            )?;
+        }
+
+        write!(f, "{}", self.stats)?;
+
+        if self.uncovered_count > 0 {
+            writeln!(
+                f,
+                "WARNING: Found {} non-covered paths, please re-run split timing script to fix!",
+                self.uncovered_count
+            )?;
+        }
+        if let Some(empty_buckets) = self.empty_buckets() {
+            writeln!(
+                f,
+                "WARNING: {} without spec files would run the entire suite, use fewer splits or 'split --on-empty'!",
+                empty_buckets
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BalanceStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Total: {:.2}s, ideal {:.2}s per bucket, lower bound {:.2}s",
+            self.total_time, self.ideal_time, self.lower_bound
+        )?;
+        writeln!(
+            f,
+            "Balance: slowest bucket {:.2}s, max/min {}, efficiency {:.1}%",
+            self.makespan,
+            match self.max_min_ratio {
+                Some(ratio) => format!("{:.2}", ratio),
+                None => "n/a (empty bucket)".to_string(),
+            },
+            self.efficiency * 100.0
+        )?;
+        if let Some((file_path, _)) = &self.bottleneck_file {
+            // the bottleneck's bucket is the lower bound, including any overhead
+            writeln!(
+                f,
+                "Bottleneck: {} takes {:.2}s on its own, more than the ideal {:.2}s",
+                file_path, self.lower_bound, self.ideal_time
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of splits to analyze, either a single number or an inclusive range
+/// (ex. '2..16') to compare them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplitRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl SplitRange {
+    /// The number of splits when the range isn't a sweep.
+    pub fn single(self) -> Option<u32> {
+        if self.start == self.end {
+            Some(self.start)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for SplitRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| format!("expected a positive number of splits, got '{}'", n))
+        };
+        let range = match s.find("..") {
+            Some(index) => SplitRange {
+                start: parse(&s[..index])?,
+                end: parse(s[index + 2..].trim_start_matches('='))?,
+            },
+            None => {
+                let n = parse(s)?;
+                SplitRange { start: n, end: n }
+            }
+        };
+
+        if range.start > range.end {
+            return Err(format!("range '{}' is empty", s));
+        }
+        Ok(range)
+    }
+}
+
+/// Balance of the split for every number of splits in a range.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Sweep {
+    pub rows: Vec<SweepRow>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SweepRow {
+    pub total_splits: u32,
+    pub makespan: f64,
+    pub max_min_ratio: Option<f64>,
+    pub efficiency: f64,
+    /// Speedup of the makespan over one split less, None for a single split
+    pub speedup: Option<f64>,
+}
+
+impl Sweep {
+    /// Analyzes every number of splits in the range with `analyze`, along with the
+    /// one before the range to compute the speedup of its first row.
+    pub fn new(
+        range: SplitRange,
+        mut analyze: impl FnMut(u32) -> Result<Analysis, failure::Error>,
+    ) -> Result<Sweep, failure::Error> {
+        let mut previous_makespan = match range.start {
+            1 => None,
+            start => Some(analyze(start - 1)?.stats.makespan),
+        };
+
+        let mut rows = vec![];
+        for total_splits in range.start..=range.end {
+            let stats = analyze(total_splits)?.stats;
+            rows.push(SweepRow {
+                total_splits,
+                makespan: stats.makespan,
+                max_min_ratio: stats.max_min_ratio,
+                efficiency: stats.efficiency,
+                speedup: previous_makespan
+                    .filter(|_| stats.makespan > 0.0)
+                    .map(|previous_makespan| previous_makespan / stats.makespan),
+            });
+            previous_makespan = Some(stats.makespan);
+        }
+        Ok(Sweep { rows })
+    }
+
+    fn cells(row: &SweepRow) -> [String; 5] {
+        [
+            row.total_splits.to_string(),
+            format!("{:.2}s", row.makespan),
+            match row.max_min_ratio {
+                Some(ratio) => format!("{:.2}", ratio),
+                None => "n/a".to_string(),
+            },
+            format!("{:.1}%", row.efficiency * 100.0),
+            match row.speedup {
+                Some(speedup) => format!("{:.2}x", speedup),
+                None => "-".to_string(),
+            },
+        ]
+    }
+
+    /// Renders the sweep as a GitHub-flavored Markdown table.
+    pub fn markdown(&self) -> String {
+        let mut markdown = format!("| {} |\n", SWEEP_HEADERS.join(" | "));
+        markdown.push_str("| ---: | ---: | ---: | ---: | ---: |\n");
+        for row in &self.rows {
+            markdown.push_str(&format!("| {} |\n", Sweep::cells(row).join(" | ")));
+        }
+        markdown
+    }
+}
+
+const SWEEP_HEADERS: [&str; 5] = ["Splits", "Makespan", "Max/min", "Efficiency", "Speedup"];
+
+impl fmt::Display for Sweep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6}  {:>10}  {:>7}  {:>10}  {:>7}",
+            SWEEP_HEADERS[0],
+            SWEEP_HEADERS[1],
+            SWEEP_HEADERS[2],
+            SWEEP_HEADERS[3],
+            SWEEP_HEADERS[4]
+        )?;
+        for row in &self.rows {
+            let cells = Sweep::cells(row);
+            writeln!(
+                f,
+                "{:>6}  {:>10}  {:>7}  {:>10}  {:>7}",
+                cells[0], cells[1], cells[2], cells[3], cells[4]
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(file_path: &str, time: Option<f64>) -> SplitFile {
+        (file_path.to_string(), time)
+    }
+
+    #[test]
+    fn it_computes_balance_stats() {
+        let analysis = Analysis::new(
+            vec![
+                vec![file("./spec/a_spec.rb", Some(12.0))],
+                vec![
+                    file("./spec/b_spec.rb", Some(4.0)),
+                    file("./spec/c_spec.rb", Some(2.0)),
+                    file("./spec/d_spec.rb", None),
+                ],
+            ],
+            0.0,
+            &CostModel::default(),
+        );
+
+        assert_eq!(
+            analysis.stats,
+            BalanceStats {
+                total_time: 18.0,
+                ideal_time: 9.0,
+                lower_bound: 12.0,
+                makespan: 12.0,
+                max_min_ratio: Some(2.0),
+                efficiency: 0.75,
+                bottleneck_file: Some(file("./spec/a_spec.rb", Some(12.0))),
+            }
+        );
+        assert_eq!(analysis.uncovered_count, 1);
+        assert_eq!(
+            analysis.to_string(),
+            "[BUCKET 1 - 12.00s] a_spec:12.00s\n\
+             [BUCKET 2 - 6.00s] b_spec:4.00s, c_spec:2.00s, d_spec:NA\n\
+             Total: 18.00s, ideal 9.00s per bucket, lower bound 12.00s\n\
+             Balance: slowest bucket 12.00s, max/min 2.00, efficiency 75.0%\n\
+             Bottleneck: ./spec/a_spec.rb takes 12.00s on its own, more than the ideal 9.00s\n\
+             WARNING: Found 1 non-covered paths, please re-run split timing script to fix!\n"
+        );
+    }
+
+    #[test]
+    fn it_renders_markdown() {
+        let analysis = Analysis::new(
+            vec![
+                vec![file("./spec/a_spec.rb", Some(12.0))],
+                vec![
+                    file("./spec/b_spec.rb", Some(4.0)),
+                    file("./spec/c_spec.rb", Some(2.0)),
+                    file("./spec/d_spec.rb", None),
+                ],
+            ],
+            0.0,
+            &CostModel::default(),
+        );
+
+        assert_eq!(
+            analysis.markdown(),
+            "| Bucket | Time | Files | Slowest files |\n\
+             | ---: | ---: | ---: | --- |\n\
+             | 1 | 12.00s | 1 | `./spec/a_spec.rb` 12.00s |\n\
+             | 2 | 6.00s | 3 | `./spec/b_spec.rb` 4.00s<br>`./spec/c_spec.rb` 2.00s |\n\
+             \n\
+             | Total | Ideal per bucket | Lower bound | Slowest bucket | Max/min | Efficiency |\n\
+             | ---: | ---: | ---: | ---: | ---: | ---: |\n\
+             | 18.00s | 9.00s | 12.00s | 12.00s | 2.00 | 75.0% |\n\
+             \n\
+             **Bottleneck:** `./spec/a_spec.rb` takes 12.00s on its own, more than the ideal 9.00s.\n\
+             \n\
+             > [!WARNING]\n\
+             > Found 1 spec files not covered by the timing information, please re-run the split timing script to fix.\n"
+        );
+    }
+
+    #[test]
+    fn it_parses_split_ranges() {
+        assert_eq!("4".parse(), Ok(SplitRange { start: 4, end: 4 }));
+        assert_eq!("2..16".parse(), Ok(SplitRange { start: 2, end: 16 }));
+        assert_eq!("2..=16".parse(), Ok(SplitRange { start: 2, end: 16 }));
+        assert!("0..4".parse::<SplitRange>().is_err());
+        assert!("4..2".parse::<SplitRange>().is_err());
+        assert!("two".parse::<SplitRange>().is_err());
+    }
+
+    #[test]
+    fn it_sweeps_the_number_of_splits() {
+        let files = [
+            file("./spec/a_spec.rb", Some(4.0)),
+            file("./spec/b_spec.rb", Some(2.0)),
+            file("./spec/c_spec.rb", Some(2.0)),
+        ];
+        let sweep = Sweep::new(SplitRange { start: 2, end: 3 }, |total_splits| {
+            let mut buckets = vec![vec![]; total_splits as usize];
+            for (index, file) in files.iter().enumerate() {
+                buckets[index % total_splits as usize].push(file.clone());
+            }
+            Ok(Analysis::new(buckets, 0.0, &CostModel::default()))
+        })
+        .expect("no errors");
+
+        assert_eq!(
+            sweep.to_string(),
+            "Splits    Makespan  Max/min  Efficiency  Speedup\n     \
+                  2       6.00s     3.00       66.7%    1.33x\n     \
+                  3       4.00s     2.00       66.7%    1.50x\n"
+        );
+    }
+
+    #[test]
+    fn it_includes_overhead_in_the_lower_bound() {
+        let analysis = Analysis::new(
+            vec![
+                vec![file("./spec/a_spec.rb", Some(3.0))],
+                vec![file("./spec/b_spec.rb", Some(3.0))],
+                vec![],
+            ],
+            0.0,
+            &CostModel {
+                node_overhead: 10.0,
+                file_overhead: 1.0,
+            },
+        );
+
+        assert_eq!(analysis.stats.lower_bound, 14.0);
+        assert_eq!(analysis.stats.max_min_ratio, None);
+        assert_eq!(
+            analysis.stats.bottleneck_file,
+            Some(file("./spec/a_spec.rb", Some(3.0)))
+        );
+    }
+
+    #[test]
+    fn it_warns_about_empty_buckets() {
+        let analysis = Analysis::new(
+            vec![vec![file("./spec/a_spec.rb", Some(3.0))], vec![], vec![]],
+            0.0,
+            &CostModel::default(),
+        );
+
+        assert!(analysis.to_string().ends_with(
+            "WARNING: BUCKET 2, 3 without spec files would run the entire suite, \
+             use fewer splits or 'split --on-empty'!\n"
+        ));
+        assert!(analysis.markdown().ends_with(
+            "> [!WARNING]\n> BUCKET 2, 3 without spec files would run the entire suite, \
+             use fewer splits or `split --on-empty`.\n"
+        ));
+    }
+}
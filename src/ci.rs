@@ -0,0 +1,356 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// CI providers the current and total split can be read from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CiProvider {
+    CircleCi,
+    Gitlab,
+    Buildkite,
+    Semaphore,
+    Travis,
+    Kubernetes,
+}
+
+/// Value of the --ci option, either a specific provider or detected from the environment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ci {
+    Auto,
+    Provider(CiProvider),
+}
+
+/// How a provider exposes the node index and the number of nodes.
+struct NodeVars {
+    index: &'static str,
+    total: Option<&'static str>,
+    one_based: bool,
+}
+
+impl CiProvider {
+    /// Providers in the order they're detected in.
+    const ALL: [CiProvider; 6] = [
+        CiProvider::CircleCi,
+        CiProvider::Gitlab,
+        CiProvider::Buildkite,
+        CiProvider::Semaphore,
+        CiProvider::Travis,
+        CiProvider::Kubernetes,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CiProvider::CircleCi => "CircleCI",
+            CiProvider::Gitlab => "GitLab CI",
+            CiProvider::Buildkite => "Buildkite",
+            CiProvider::Semaphore => "Semaphore",
+            CiProvider::Travis => "Travis CI",
+            CiProvider::Kubernetes => "Kubernetes indexed Job",
+        }
+    }
+
+    /// Environment variable that is always set when running on the provider.
+    fn detect_var(self) -> &'static str {
+        match self {
+            CiProvider::CircleCi => "CIRCLECI",
+            CiProvider::Gitlab => "GITLAB_CI",
+            CiProvider::Buildkite => "BUILDKITE",
+            CiProvider::Semaphore => "SEMAPHORE",
+            CiProvider::Travis => "TRAVIS",
+            CiProvider::Kubernetes => "JOB_COMPLETION_INDEX",
+        }
+    }
+
+    fn node_vars(self) -> NodeVars {
+        match self {
+            CiProvider::CircleCi => NodeVars {
+                index: "CIRCLE_NODE_INDEX",
+                total: Some("CIRCLE_NODE_TOTAL"),
+                one_based: false,
+            },
+            CiProvider::Gitlab => NodeVars {
+                index: "CI_NODE_INDEX",
+                total: Some("CI_NODE_TOTAL"),
+                one_based: true,
+            },
+            CiProvider::Buildkite => NodeVars {
+                index: "BUILDKITE_PARALLEL_JOB",
+                total: Some("BUILDKITE_PARALLEL_JOB_COUNT"),
+                one_based: false,
+            },
+            CiProvider::Semaphore => NodeVars {
+                index: "SEMAPHORE_JOB_INDEX",
+                total: Some("SEMAPHORE_JOB_COUNT"),
+                one_based: true,
+            },
+            // Travis has no built-in parallelism, the convention is to define
+            // 0-based CI_NODE_INDEX and CI_NODE_TOTAL in the build matrix
+            CiProvider::Travis => NodeVars {
+                index: "CI_NODE_INDEX",
+                total: Some("CI_NODE_TOTAL"),
+                one_based: false,
+            },
+            // Indexed Jobs don't expose the number of completions to the pod
+            CiProvider::Kubernetes => NodeVars {
+                index: "JOB_COMPLETION_INDEX",
+                total: None,
+                one_based: false,
+            },
+        }
+    }
+}
+
+impl Ci {
+    pub fn variants() -> &'static [&'static str] {
+        &[
+            "auto",
+            "circleci",
+            "gitlab",
+            "buildkite",
+            "semaphore",
+            "travis",
+            "kubernetes",
+        ]
+    }
+}
+
+impl FromStr for Ci {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Ci::Auto),
+            "circleci" => Ok(Ci::Provider(CiProvider::CircleCi)),
+            "gitlab" => Ok(Ci::Provider(CiProvider::Gitlab)),
+            "buildkite" => Ok(Ci::Provider(CiProvider::Buildkite)),
+            "semaphore" => Ok(Ci::Provider(CiProvider::Semaphore)),
+            "travis" => Ok(Ci::Provider(CiProvider::Travis)),
+            "kubernetes" => Ok(Ci::Provider(CiProvider::Kubernetes)),
+            _ => Err(format!(
+                "unknown CI provider '{}', expected one of: {}",
+                s,
+                Ci::variants().join(", ")
+            )),
+        }
+    }
+}
+
+/// The current node as seen from the CI environment, with the current split
+/// normalized to be 0-based.
+#[derive(Debug, PartialEq)]
+pub struct CiNode {
+    pub provider: CiProvider,
+    pub current_split: u32,
+    /// None if the provider doesn't expose the number of nodes
+    pub total_splits: Option<u32>,
+    vars: Vec<(&'static str, String)>,
+}
+
+impl CiNode {
+    /// Reads the current node from the environment, where `env` looks up an
+    /// environment variable (ex. `|name| std::env::var(name).ok()`).
+    pub fn from_env(
+        ci: Ci,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<CiNode, failure::Error> {
+        let provider = match ci {
+            Ci::Provider(provider) => provider,
+            Ci::Auto => match CiProvider::ALL
+                .iter()
+                .find(|p| env(p.detect_var()).is_some())
+            {
+                Some(provider) => *provider,
+                None => failure::bail!(
+                    "could not detect the CI provider from the environment, \
+                     pass --total-splits and --current-split instead"
+                ),
+            },
+        };
+
+        let node_vars = provider.node_vars();
+        let mut vars = vec![];
+        let mut read_var = |name: &'static str| -> Result<Option<u32>, failure::Error> {
+            match env(name) {
+                Some(value) => {
+                    let parsed = value.trim().parse::<u32>().map_err(|_| {
+                        failure::format_err!(
+                            "{} should be a non-negative integer, got '{}'",
+                            name,
+                            value
+                        )
+                    })?;
+                    vars.push((name, value));
+                    Ok(Some(parsed))
+                }
+                None => Ok(None),
+            }
+        };
+
+        let index = read_var(node_vars.index)?;
+        let total = match node_vars.total {
+            Some(total_var) => read_var(total_var)?,
+            None => None,
+        };
+
+        // a job without parallelism enabled doesn't have any of the variables either,
+        // but a split computed for it can't tell whether it's the only node
+        let index = match index {
+            Some(index) => index,
+            None => failure::bail!(
+                "detected {} but {} is not set, enable parallelism or pass \
+                 --total-splits and --current-split instead",
+                provider.name(),
+                node_vars.index
+            ),
+        };
+        let current_split = if node_vars.one_based {
+            match index.checked_sub(1) {
+                Some(current_split) => current_split,
+                None => failure::bail!("{} is 1-based, got 0", node_vars.index),
+            }
+        } else {
+            index
+        };
+
+        Ok(CiNode {
+            provider,
+            current_split,
+            total_splits: total,
+            vars,
+        })
+    }
+}
+
+impl fmt::Display for CiNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Detected {}: current split {}",
+            self.provider.name(),
+            self.current_split
+        )?;
+        if let Some(total_splits) = self.total_splits {
+            write!(f, " of {}", total_splits)?;
+        }
+
+        let vars = self
+            .vars
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>();
+        if !vars.is_empty() {
+            write!(f, " ({})", vars.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(ci: Ci, vars: &[(&str, &str)]) -> Result<CiNode, failure::Error> {
+        CiNode::from_env(ci, |name| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        })
+    }
+
+    fn splits(vars: &[(&str, &str)]) -> (CiProvider, u32, Option<u32>) {
+        let node = node(Ci::Auto, vars).expect("no errors");
+        (node.provider, node.current_split, node.total_splits)
+    }
+
+    #[test]
+    fn it_normalizes_each_providers_index() {
+        assert_eq!(
+            splits(&[
+                ("CIRCLECI", "true"),
+                ("CIRCLE_NODE_INDEX", "0"),
+                ("CIRCLE_NODE_TOTAL", "4")
+            ]),
+            (CiProvider::CircleCi, 0, Some(4))
+        );
+        assert_eq!(
+            splits(&[
+                ("GITLAB_CI", "true"),
+                ("CI_NODE_INDEX", "1"),
+                ("CI_NODE_TOTAL", "4")
+            ]),
+            (CiProvider::Gitlab, 0, Some(4))
+        );
+        assert_eq!(
+            splits(&[
+                ("BUILDKITE", "true"),
+                ("BUILDKITE_PARALLEL_JOB", "3"),
+                ("BUILDKITE_PARALLEL_JOB_COUNT", "4")
+            ]),
+            (CiProvider::Buildkite, 3, Some(4))
+        );
+        assert_eq!(
+            splits(&[
+                ("SEMAPHORE", "true"),
+                ("SEMAPHORE_JOB_INDEX", "4"),
+                ("SEMAPHORE_JOB_COUNT", "4")
+            ]),
+            (CiProvider::Semaphore, 3, Some(4))
+        );
+        assert_eq!(
+            splits(&[
+                ("TRAVIS", "true"),
+                ("CI_NODE_INDEX", "1"),
+                ("CI_NODE_TOTAL", "4")
+            ]),
+            (CiProvider::Travis, 1, Some(4))
+        );
+        assert_eq!(
+            splits(&[("JOB_COMPLETION_INDEX", "2")]),
+            (CiProvider::Kubernetes, 2, None)
+        );
+    }
+
+    #[test]
+    fn it_errors_without_the_index_of_a_detected_provider() {
+        let err = node(Ci::Auto, &[("GITLAB_CI", "true")]).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("detected GitLab CI but CI_NODE_INDEX is not set"));
+        assert!(node(
+            Ci::Auto,
+            &[("CIRCLECI", "true"), ("CIRCLE_NODE_TOTAL", "4")]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_errors_on_invalid_environments() {
+        assert!(node(Ci::Auto, &[]).is_err());
+        assert!(node(
+            Ci::Provider(CiProvider::Gitlab),
+            &[("CI_NODE_INDEX", "0"), ("CI_NODE_TOTAL", "4")]
+        )
+        .is_err());
+        assert!(node(
+            Ci::Provider(CiProvider::CircleCi),
+            &[("CIRCLE_NODE_INDEX", "first"), ("CIRCLE_NODE_TOTAL", "4")]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn it_describes_the_detected_node() {
+        let node = node(
+            Ci::Auto,
+            &[
+                ("GITLAB_CI", "true"),
+                ("CI_NODE_INDEX", "2"),
+                ("CI_NODE_TOTAL", "4"),
+            ],
+        )
+        .expect("no errors");
+        assert_eq!(
+            node.to_string(),
+            "Detected GitLab CI: current split 1 of 4 (CI_NODE_INDEX=2, CI_NODE_TOTAL=4)"
+        );
+    }
+}
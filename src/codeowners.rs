@@ -0,0 +1,89 @@
+use crate::glob::Glob;
+use std::path::{Path, PathBuf};
+
+/// Locations GitHub looks for a CODEOWNERS file in, in order.
+const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Owners of paths as defined by a CODEOWNERS file.
+#[derive(Debug)]
+pub struct Codeowners {
+    rules: Vec<(Glob, Vec<String>)>,
+}
+
+impl Codeowners {
+    pub fn parse(codeowners: &str) -> Result<Codeowners, failure::Error> {
+        let mut rules = vec![];
+        for line in codeowners.lines() {
+            let line = match line.find('#') {
+                Some(index) => &line[..index],
+                None => line,
+            };
+            let mut fields = line.split_whitespace();
+            let pattern = match fields.next() {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            rules.push((Glob::new(pattern)?, fields.map(String::from).collect()));
+        }
+        Ok(Codeowners { rules })
+    }
+
+    /// Finds the CODEOWNERS file in the locations GitHub looks in, relative to `root`.
+    pub fn find(root: &Path) -> Option<PathBuf> {
+        CODEOWNERS_PATHS
+            .iter()
+            .map(|path| root.join(path))
+            .find(|path| path.is_file())
+    }
+
+    /// Owners of the path, the last matching rule takes precedence. Paths matched
+    /// by a rule without owners are unowned.
+    pub fn owners(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(path))
+            .map(|(_, owners)| owners.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_uses_the_last_matching_rule() {
+        let codeowners = Codeowners::parse(
+            "# Default owners\n\
+             * @org/core\n\
+             \n\
+             /spec/models/ @org/data @alice # models\n\
+             /spec/models/legacy/\n",
+        )
+        .expect("valid CODEOWNERS");
+
+        assert_eq!(codeowners.owners("./spec/lib/a_spec.rb"), ["@org/core"]);
+        assert_eq!(
+            codeowners.owners("./spec/models/app_spec.rb"),
+            ["@org/data", "@alice"]
+        );
+        assert!(codeowners
+            .owners("./spec/models/legacy/old_spec.rb")
+            .is_empty());
+    }
+
+    #[test]
+    fn it_doesnt_match_nested_files_with_a_trailing_star() {
+        let codeowners = Codeowners::parse(
+            "docs/* @org/docs
+",
+        )
+        .expect("valid CODEOWNERS");
+
+        assert_eq!(codeowners.owners("docs/getting-started.md"), ["@org/docs"]);
+        assert!(codeowners
+            .owners("docs/build-app/troubleshooting.md")
+            .is_empty());
+    }
+}
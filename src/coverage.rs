@@ -0,0 +1,206 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Reverse index from application source files to the spec files that executed them.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoverageIndex {
+    pub sources: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl CoverageIndex {
+    /// Adds every spec file in the SimpleCov result set to the index. The result set
+    /// is expected to be recorded per spec file, ie. each command name is the path of
+    /// the spec file that produced the coverage underneath it.
+    pub fn add_resultset(
+        &mut self,
+        resultset: impl AsRef<str>,
+        root: &Path,
+    ) -> Result<(), failure::Error> {
+        let resultset: BTreeMap<String, Value> = serde_json::from_str(resultset.as_ref())?;
+        for (command_name, result) in resultset {
+            let spec_path = normalize_path(&command_name, root);
+            let coverage = match result.get("coverage").and_then(Value::as_object) {
+                Some(coverage) => coverage,
+                None => continue,
+            };
+
+            for (source_path, source_coverage) in coverage {
+                if !is_covered(source_coverage) {
+                    continue;
+                }
+
+                self.sources
+                    .entry(normalize_path(source_path, root))
+                    .or_default()
+                    .insert(spec_path.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the spec files impacted by the changed files. Changed spec files are
+    /// always impacted, even if they don't show up in the coverage data.
+    pub fn impacted_specs(&self, changed_files: &[String], root: &Path) -> BTreeSet<String> {
+        let mut impacted_specs = BTreeSet::new();
+        for changed_file in changed_files {
+            let changed_file = normalize_path(changed_file, root);
+            if let Some(spec_paths) = self.sources.get(&changed_file) {
+                impacted_specs.extend(spec_paths.iter().cloned());
+            }
+
+            if changed_file.ends_with("_spec.rb") {
+                impacted_specs.insert(changed_file);
+            }
+        }
+        impacted_specs
+    }
+
+    /// Returns the changed files that are neither spec files nor in the index, whose
+    /// impacted specs are unknown (ex. new files, config/ or spec/spec_helper.rb).
+    pub fn unindexed_files(&self, changed_files: &[String], root: &Path) -> Vec<String> {
+        changed_files
+            .iter()
+            .map(|changed_file| normalize_path(changed_file, root))
+            .filter(|changed_file| {
+                !self.sources.contains_key(changed_file) && !changed_file.ends_with("_spec.rb")
+            })
+            .collect()
+    }
+}
+
+/// Returns the path the coverage index is stored at, next to the timing file.
+///
+/// Ex. 'rspec-parsed.txt' has its coverage index at 'rspec-parsed.coverage-index.json'
+pub fn index_path_for(timing_file: &Path) -> PathBuf {
+    let file_stem = timing_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    timing_file.with_file_name(format!("{}.coverage-index.json", file_stem))
+}
+
+/// Normalizes paths into the './spec/models/app_spec.rb' format rspec reports in,
+/// stripping the root from absolute paths.
+pub fn normalize_path(path: &str, root: &Path) -> String {
+    let path = Path::new(path);
+    let path = path.strip_prefix(root).unwrap_or(path);
+    let path = path.strip_prefix(".").unwrap_or(path);
+    if path.is_absolute() {
+        return path.to_string_lossy().into_owned();
+    }
+    format!("./{}", path.to_string_lossy())
+}
+
+/// SimpleCov stores line coverage either as an array of hit counts (< 0.18)
+/// or as an object with the hit counts under "lines".
+fn is_covered(source_coverage: &Value) -> bool {
+    let lines = match source_coverage {
+        Value::Array(lines) => lines,
+        Value::Object(coverage) => match coverage.get("lines").and_then(Value::as_array) {
+            Some(lines) => lines,
+            None => return false,
+        },
+        _ => return false,
+    };
+
+    lines
+        .iter()
+        .any(|hits| hits.as_u64().map(|hits| hits > 0).unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_builds_a_reverse_index() {
+        let mut index = CoverageIndex::default();
+        index
+            .add_resultset(
+                r####"{
+                    "./spec/models/app_spec.rb": {
+                        "coverage": {
+                            "/code/app/models/app.rb": {"lines": [null, 1, 0]},
+                            "/code/app/models/player.rb": {"lines": [null, 0, 0]}
+                        },
+                        "timestamp": 1546300800
+                    },
+                    "/code/spec/models/player_spec.rb": {
+                        "coverage": {
+                            "/code/app/models/app.rb": [null, 2],
+                            "/code/app/models/player.rb": [3]
+                        },
+                        "timestamp": 1546300800
+                    }
+                }"####,
+                Path::new("/code"),
+            )
+            .expect("no errors");
+
+        let root = Path::new("/code");
+        assert_eq!(
+            index.impacted_specs(&["app/models/app.rb".to_string()], root),
+            vec![
+                "./spec/models/app_spec.rb".to_string(),
+                "./spec/models/player_spec.rb".to_string()
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(
+            index.impacted_specs(
+                &[
+                    "app/models/player.rb".to_string(),
+                    "spec/lib/shard_spec.rb".to_string()
+                ],
+                root
+            ),
+            vec![
+                "./spec/lib/shard_spec.rb".to_string(),
+                "./spec/models/player_spec.rb".to_string()
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert!(index
+            .impacted_specs(&["README.md".to_string()], root)
+            .is_empty());
+    }
+
+    #[test]
+    fn it_lists_changed_files_missing_from_the_index() {
+        let mut index = CoverageIndex::default();
+        index
+            .sources
+            .entry("./app/models/app.rb".to_string())
+            .or_default()
+            .insert("./spec/models/app_spec.rb".to_string());
+
+        assert_eq!(
+            index.unindexed_files(
+                &[
+                    "app/models/app.rb".to_string(),
+                    "app/models/new.rb".to_string(),
+                    "spec/spec_helper.rb".to_string(),
+                    "spec/models/new_spec.rb".to_string(),
+                ],
+                Path::new("/code")
+            ),
+            vec![
+                "./app/models/new.rb".to_string(),
+                "./spec/spec_helper.rb".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn index_is_stored_next_to_timing_file() {
+        assert_eq!(
+            index_path_for(Path::new("ci/rspec-parsed.txt")),
+            PathBuf::from("ci/rspec-parsed.coverage-index.json")
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use failure::Fail;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors of invocations that can't work, each with its own exit code so that CI
+/// scripts can tell them apart from failing specs. Any other error exits with 1.
+#[derive(Debug)]
+pub enum Error {
+    InvalidSplitIndex {
+        current_split: u32,
+        total_splits: u32,
+    },
+    MissingSpecDir {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    MalformedTimingFile {
+        path: PathBuf,
+        cause: serde_json::Error,
+    },
+    UnreadableInput {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    EmptySplit {
+        current_split: u32,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidSplitIndex {
+                current_split,
+                total_splits,
+            } => write!(
+                f,
+                "current split should be between [0..{}), got {}",
+                total_splits, current_split
+            ),
+            Error::MissingSpecDir { path, .. } => {
+                write!(f, "can't read the spec directory {:?}", path)
+            }
+            Error::MalformedTimingFile { path, .. } => {
+                write!(f, "malformed timing file {:?}", path)
+            }
+            Error::UnreadableInput { path, .. } => write!(f, "can't read {:?}", path),
+            Error::EmptySplit { current_split } => {
+                write!(f, "split {} has no spec files", current_split)
+            }
+        }
+    }
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self {
+            Error::InvalidSplitIndex { .. } | Error::EmptySplit { .. } => None,
+            Error::MissingSpecDir { cause, .. } | Error::UnreadableInput { cause, .. } => {
+                Some(cause)
+            }
+            Error::MalformedTimingFile { cause, .. } => Some(cause),
+        }
+    }
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::InvalidSplitIndex { .. } => 3,
+            Error::MissingSpecDir { .. } => 4,
+            Error::MalformedTimingFile { .. } => 5,
+            Error::UnreadableInput { .. } => 6,
+            Error::EmptySplit { .. } => 7,
+        }
+    }
+
+    /// What to try next, when there is something more useful to say than the error.
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Error::InvalidSplitIndex { total_splits, .. } if *total_splits > 0 => Some(format!(
+                "--current-split is 0-based, the last of {} splits is {}",
+                total_splits,
+                total_splits - 1
+            )),
+            Error::InvalidSplitIndex { .. } => None,
+            Error::MissingSpecDir { .. } => {
+                Some("run from the root of the project, the directory containing spec/".into())
+            }
+            Error::MalformedTimingFile { .. } => {
+                Some("timing files are written by 'parse' from the JUnit output of rspec".into())
+            }
+            Error::UnreadableInput { .. } => None,
+            Error::EmptySplit { .. } => Some(
+                "use fewer splits than spec files, or --on-empty sentinel or noop to print a placeholder".into(),
+            ),
+        }
+    }
+}
+
+/// Checks that the current split is one of the total splits.
+pub fn check_split_index(current_split: u32, total_splits: u32) -> Result<(), Error> {
+    if current_split >= total_splits {
+        return Err(Error::InvalidSplitIndex {
+            current_split,
+            total_splits,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+/// Incremental 64-bit FNV-1a hasher used to fingerprint the inputs of a split.
+///
+/// Unlike `std::collections::hash_map::DefaultHasher` the output is stable across
+/// Rust versions and platforms, which is required since fingerprints produced by
+/// one binary are compared by another.
+pub struct Fingerprinter {
+    hash: u64,
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+impl Fingerprinter {
+    pub fn new() -> Fingerprinter {
+        Fingerprinter {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+
+    /// Adds a field to the fingerprint. Fields are length-prefixed so that
+    /// ["ab", "c"] and ["a", "bc"] produce different fingerprints.
+    pub fn field(mut self, value: impl AsRef<[u8]>) -> Fingerprinter {
+        let value = value.as_ref();
+        self.write(&(value.len() as u64).to_le_bytes());
+        self.write(value);
+        self
+    }
+
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.hash)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= u64::from(*byte);
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// Fingerprints everything that determines the result of a split: the timing file
+/// contents, the discovered spec files, the number of splits and the strategy.
+pub fn split_fingerprint(
+    timing_output: &str,
+    spec_paths: &[String],
+    total_splits: u32,
+    strategy: &str,
+) -> String {
+    let mut spec_paths = spec_paths.to_vec();
+    spec_paths.sort();
+
+    let mut fingerprinter = Fingerprinter::new()
+        .field(timing_output)
+        .field(spec_paths.len().to_string());
+    for spec_path in spec_paths {
+        fingerprinter = fingerprinter.field(spec_path);
+    }
+    fingerprinter
+        .field(total_splits.to_string())
+        .field(strategy)
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_fnv_1a() {
+        let mut fingerprinter = Fingerprinter::new();
+        fingerprinter.write(b"a");
+        assert_eq!(fingerprinter.finish(), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn it_is_independent_of_spec_order() {
+        let specs = vec![
+            "./spec/a_spec.rb".to_string(),
+            "./spec/b_spec.rb".to_string(),
+        ];
+        let reversed = specs.iter().cloned().rev().collect::<Vec<_>>();
+        assert_eq!(
+            split_fingerprint("[]", &specs, 4, "greedy"),
+            split_fingerprint("[]", &reversed, 4, "greedy")
+        );
+        assert_ne!(
+            split_fingerprint("[]", &specs, 4, "greedy"),
+            split_fingerprint("[]", &specs, 5, "greedy")
+        );
+        assert_ne!(
+            split_fingerprint("[]", &specs, 4, "greedy"),
+            split_fingerprint("[ ]", &specs, 4, "greedy")
+        );
+        assert_ne!(
+            split_fingerprint("[]", &specs, 4, "greedy"),
+            split_fingerprint("[]", &specs, 4, "other")
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use regex::Regex;
+
+/// Path pattern with gitignore / CODEOWNERS semantics, matched against paths
+/// relative to the project root (with or without a leading './').
+///
+/// - `*` and `?` don't match across directories, `**` does
+/// - patterns starting with or containing a `/` are anchored to the root,
+///   others match at any depth
+/// - patterns matching a directory match everything underneath it, and
+///   patterns ending with a `/` only match directories
+/// - patterns ending with a single `*` (ex. `docs/*`) only match files, not
+///   what is nested in the directories they match
+#[derive(Debug, Clone)]
+pub struct Glob {
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, failure::Error> {
+        let mut body = pattern.trim_start_matches("./");
+        let directory_only = body.ends_with('/');
+        body = body.trim_end_matches('/');
+        let anchored = body.contains('/');
+        let files_only = body.ends_with('*') && !body.ends_with("**");
+        body = body.trim_start_matches('/');
+        if body.is_empty() {
+            failure::bail!("empty path pattern '{}'", pattern);
+        }
+
+        let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+        let chars = body.chars().collect::<Vec<_>>();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    if chars.get(i + 2) == Some(&'/') {
+                        regex.push_str("(?:.*/)?");
+                        i += 3;
+                    } else {
+                        regex.push_str(".*");
+                        i += 2;
+                    }
+                    continue;
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+            i += 1;
+        }
+        regex.push_str(if directory_only {
+            "/.*$"
+        } else if files_only {
+            "$"
+        } else {
+            "(?:/.*)?$"
+        });
+
+        Ok(Glob {
+            regex: Regex::new(&regex)?,
+        })
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path.trim_start_matches("./"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).expect("valid pattern").is_match(path)
+    }
+
+    #[test]
+    fn it_matches_unanchored_patterns_at_any_depth() {
+        assert!(matches("*.rb", "./spec/models/app_spec.rb"));
+        assert!(matches("models", "./spec/models/app_spec.rb"));
+        assert!(matches("app_spec.rb", "spec/models/app_spec.rb"));
+        assert!(!matches("*.js", "./spec/models/app_spec.rb"));
+    }
+
+    #[test]
+    fn it_anchors_patterns_with_slashes() {
+        assert!(matches("/spec/models/", "./spec/models/app_spec.rb"));
+        assert!(matches("spec/models", "./spec/models/nested/app_spec.rb"));
+        assert!(!matches("/models", "./spec/models/app_spec.rb"));
+        assert!(!matches("spec/*.rb", "./spec/models/app_spec.rb"));
+        assert!(matches("spec/*/app_spec.rb", "./spec/models/app_spec.rb"));
+        assert!(matches("spec/*", "./spec/app_spec.rb"));
+        assert!(!matches("spec/*", "./spec/models/app_spec.rb"));
+    }
+
+    #[test]
+    fn it_matches_across_directories_with_double_stars() {
+        assert!(matches("spec/**/app_spec.rb", "./spec/app_spec.rb"));
+        assert!(matches(
+            "spec/**/app_spec.rb",
+            "./spec/models/nested/app_spec.rb"
+        ));
+        assert!(matches("spec/**", "./spec/models/app_spec.rb"));
+        assert!(matches("**/models/*_spec.rb", "./spec/models/app_spec.rb"));
+    }
+
+    #[test]
+    fn it_only_matches_directories_with_trailing_slashes() {
+        assert!(!matches("app_spec.rb/", "./spec/models/app_spec.rb"));
+        assert!(matches("models/", "./spec/models/app_spec.rb"));
+    }
+}
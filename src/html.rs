@@ -0,0 +1,206 @@
+use crate::analysis::Analysis;
+use std::fmt::Write;
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em; color: #24292e; }
+h1 { font-size: 1.4em; }
+dl { display: grid; grid-template-columns: max-content auto; gap: 0.2em 1em; }
+dt { font-weight: bold; }
+.bucket { display: flex; align-items: center; margin: 0.3em 0; }
+.label { width: 12em; flex-shrink: 0; font-variant-numeric: tabular-nums; }
+.bar { display: flex; flex-grow: 1; height: 1.6em; background: #f6f8fa; }
+.segment { height: 100%; box-sizing: border-box; border-right: 1px solid #fff; }
+.segment:hover { opacity: 0.7; }
+.overhead { background: #d1d5da; }
+.uncovered { min-width: 4px; background: repeating-linear-gradient(45deg, #d73a49, #d73a49 3px, #fff 3px, #fff 6px); }
+table { border-collapse: collapse; margin-top: 1em; }
+th, td { padding: 0.2em 0.8em; text-align: left; border-bottom: 1px solid #e1e4e8; }
+th { cursor: pointer; user-select: none; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }
+tr.uncovered td { background: #ffeef0; }
+.warning { color: #d73a49; font-weight: bold; }
+"#;
+
+const SCRIPT: &str = r#"
+document.querySelectorAll("th").forEach(function (th, column) {
+  th.addEventListener("click", function () {
+    var tbody = th.closest("table").querySelector("tbody");
+    var ascending = th.dataset.order !== "asc";
+    th.dataset.order = ascending ? "asc" : "desc";
+    Array.from(tbody.rows)
+      .sort(function (a, b) {
+        var x = a.cells[column].dataset.value, y = b.cells[column].dataset.value;
+        var order = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+        return ascending ? order : -order;
+      })
+      .forEach(function (row) { tbody.appendChild(row); });
+  });
+});
+"#;
+
+/// Renders the analysis as a single HTML page without any external assets, with a
+/// stacked bar of the files in each bucket and a sortable table of every file.
+pub fn analysis_html(analysis: &Analysis) -> String {
+    let stats = &analysis.stats;
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>rspec-timing-tool analysis</title>\n");
+    write!(html, "<style>{}</style>\n</head>\n<body>\n", STYLE).unwrap();
+    writeln!(
+        html,
+        "<h1>{} spec files split into {} buckets</h1>",
+        analysis
+            .buckets
+            .iter()
+            .map(|b| b.files.len())
+            .sum::<usize>(),
+        analysis.buckets.len()
+    )
+    .unwrap();
+
+    html.push_str("<dl>\n");
+    let mut stat = |name: &str, value: String| {
+        writeln!(html, "<dt>{}</dt><dd>{}</dd>", name, escape(&value)).unwrap();
+    };
+    stat("Total", format!("{:.2}s", stats.total_time));
+    stat("Ideal per bucket", format!("{:.2}s", stats.ideal_time));
+    stat("Lower bound", format!("{:.2}s", stats.lower_bound));
+    stat("Slowest bucket", format!("{:.2}s", stats.makespan));
+    stat(
+        "Max/min",
+        match stats.max_min_ratio {
+            Some(ratio) => format!("{:.2}", ratio),
+            None => "n/a (empty bucket)".to_string(),
+        },
+    );
+    stat("Efficiency", format!("{:.1}%", stats.efficiency * 100.0));
+    if let Some((file_path, _)) = &stats.bottleneck_file {
+        stat(
+            "Bottleneck",
+            format!("{} ({:.2}s)", file_path, stats.lower_bound),
+        );
+    }
+    html.push_str("</dl>\n");
+    if analysis.uncovered_count > 0 {
+        writeln!(
+            html,
+            "<p class=\"warning\">{} spec files aren't covered by the timing information</p>",
+            analysis.uncovered_count
+        )
+        .unwrap();
+    }
+
+    let width = |time: f64| {
+        if stats.makespan > 0.0 {
+            time / stats.makespan * 100.0
+        } else {
+            0.0
+        }
+    };
+    for (index, bucket) in analysis.buckets.iter().enumerate() {
+        write!(
+            html,
+            "<div class=\"bucket\"><span class=\"label\">Bucket {} - {:.2}s</span><div class=\"bar\">",
+            index + 1,
+            bucket.predicted_time
+        )
+        .unwrap();
+
+        let file_time = bucket
+            .files
+            .iter()
+            .map(|(_, t)| t.unwrap_or(0.0))
+            .sum::<f64>();
+        let overhead = bucket.predicted_time - file_time;
+        if overhead > 0.0 {
+            write!(
+                html,
+                "<div class=\"segment overhead\" style=\"width: {:.3}%\" title=\"Overhead: {:.2}s\"></div>",
+                width(overhead),
+                overhead
+            )
+            .unwrap();
+        }
+        for (file_index, (file_path, time)) in bucket.files.iter().enumerate() {
+            match time {
+                Some(time) => write!(
+                    html,
+                    "<div class=\"segment\" style=\"width: {:.3}%; background: hsl({}, 60%, 55%)\" title=\"{}: {:.2}s\"></div>",
+                    width(*time),
+                    (file_index * 47) % 360,
+                    escape(file_path),
+                    time
+                ),
+                None => write!(
+                    html,
+                    "<div class=\"segment uncovered\" title=\"{}: no timing information\"></div>",
+                    escape(file_path)
+                ),
+            }
+            .unwrap();
+        }
+        html.push_str("</div></div>\n");
+    }
+
+    html.push_str("<table>\n<thead><tr><th>File</th><th>Bucket</th><th>Time (s)</th><th>Share of bucket</th></tr></thead>\n<tbody>\n");
+    for (index, bucket) in analysis.buckets.iter().enumerate() {
+        for (file_path, time) in &bucket.files {
+            let share = match time {
+                Some(time) if bucket.predicted_time > 0.0 => time / bucket.predicted_time * 100.0,
+                _ => 0.0,
+            };
+            writeln!(
+                html,
+                "<tr{}><td data-value=\"{path}\">{path}</td><td class=\"number\" data-value=\"{bucket}\">{bucket}</td><td class=\"number\" data-value=\"{time}\">{time_label}</td><td class=\"number\" data-value=\"{share}\">{share:.1}%</td></tr>",
+                if time.is_none() { " class=\"uncovered\"" } else { "" },
+                path = escape(file_path),
+                bucket = index + 1,
+                time = time.unwrap_or(0.0),
+                time_label = match time {
+                    Some(time) => format!("{:.2}", time),
+                    None => "uncovered".to_string(),
+                },
+                share = share
+            )
+            .unwrap();
+        }
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    write!(html, "<script>{}</script>\n</body>\n</html>\n", SCRIPT).unwrap();
+    html
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timings::CostModel;
+
+    #[test]
+    fn it_renders_buckets_and_highlights_uncovered_files() {
+        let analysis = Analysis::new(
+            vec![
+                vec![("./spec/a_spec.rb".to_string(), Some(2.0))],
+                vec![("./spec/<b>_spec.rb".to_string(), None)],
+            ],
+            0.0,
+            &CostModel::default(),
+        );
+        let html = analysis_html(&analysis);
+
+        assert!(html.contains(r#"title="./spec/a_spec.rb: 2.00s""#));
+        assert!(
+            html.contains(r#"<tr class="uncovered"><td data-value="./spec/&lt;b&gt;_spec.rb">"#)
+        );
+        assert!(!html.contains("<b>"));
+        assert!(!html.contains("http"));
+    }
+}
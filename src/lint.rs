@@ -0,0 +1,181 @@
+use crate::pre_bucketed::{self, Bucket};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// Problem with a pre-bucketed file that would make the splits run the wrong files.
+#[derive(Debug, PartialEq)]
+pub enum Violation {
+    /// Number of buckets differs from --total-splits
+    BucketCount { expected: u32, actual: usize },
+    /// File is listed in more than one split and would run more than once
+    Duplicate {
+        file_path: String,
+        splits: Vec<usize>,
+    },
+    /// File no longer exists in the spec tree
+    Missing { file_path: String, split: usize },
+    /// Glob or directory doesn't match any spec file that isn't in an earlier split
+    UnmatchedPattern { pattern: String, split: usize },
+    /// Spec file isn't in any split
+    Uncovered { file_path: String },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::BucketCount { expected, actual } => write!(
+                f,
+                "expected {} splits (--total-splits) but the file has {}",
+                expected, actual
+            ),
+            Violation::Duplicate { file_path, splits } => write!(
+                f,
+                "{} is in splits {} and would run {} times",
+                file_path,
+                splits
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                splits.len()
+            ),
+            Violation::Missing { file_path, split } => {
+                write!(f, "{} in split {} doesn't exist", file_path, split)
+            }
+            Violation::UnmatchedPattern { pattern, split } => write!(
+                f,
+                "{} in split {} doesn't match any spec file",
+                pattern, split
+            ),
+            Violation::Uncovered { file_path } => write!(
+                f,
+                "{} isn't in any split and would be added to the last split",
+                file_path
+            ),
+        }
+    }
+}
+
+/// Checks the pre-bucketed splits against the number of splits (if known) and the
+/// spec files, where splits are 0-based like --current-split. Spec files that aren't
+/// in any split are only a problem if no split is marked as overflow.
+pub fn lint_buckets(
+    buckets: &[Bucket],
+    total_splits: Option<u32>,
+    spec_paths: &[String],
+) -> Result<Vec<Violation>, failure::Error> {
+    let mut violations = vec![];
+    if let Some(total_splits) = total_splits {
+        if total_splits as usize != buckets.len() {
+            violations.push(Violation::BucketCount {
+                expected: total_splits,
+                actual: buckets.len(),
+            });
+        }
+    }
+
+    let spec_paths_set = spec_paths
+        .iter()
+        .map(String::as_str)
+        .collect::<HashSet<_>>();
+    let mut splits_by_path: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (split, bucket) in buckets.iter().enumerate() {
+        for (file_path, _) in bucket.files() {
+            splits_by_path.entry(file_path).or_default().push(split);
+            if !spec_paths_set.contains(file_path.as_str()) {
+                violations.push(Violation::Missing {
+                    file_path: file_path.clone(),
+                    split,
+                });
+            }
+        }
+    }
+
+    let resolution = pre_bucketed::resolve_buckets(buckets, spec_paths)?;
+    for (split, bucket) in buckets.iter().enumerate() {
+        for (pattern, matches) in bucket.patterns().zip(&resolution.pattern_matches[split]) {
+            if *matches == 0 {
+                violations.push(Violation::UnmatchedPattern {
+                    pattern: pattern.to_string(),
+                    split,
+                });
+            }
+        }
+    }
+
+    for (file_path, splits) in &splits_by_path {
+        if splits.len() > 1 {
+            violations.push(Violation::Duplicate {
+                file_path: file_path.to_string(),
+                splits: splits.clone(),
+            });
+        }
+    }
+
+    if resolution.overflow.is_empty() {
+        violations.extend(
+            resolution
+                .leftovers
+                .into_iter()
+                .map(|file_path| Violation::Uncovered { file_path }),
+        );
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buckets(json: &str) -> Vec<Bucket> {
+        serde_json::from_str(json).expect("valid pre-bucketed file")
+    }
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn it_flags_every_violation() {
+        let violations = lint_buckets(
+            &buckets(
+                r#"[
+                    [["./spec/a_spec.rb", 1], ["./spec/gone_spec.rb", 1], "spec/models"],
+                    [["./spec/a_spec.rb", 1]]
+                ]"#,
+            ),
+            Some(3),
+            &paths(&["./spec/a_spec.rb", "./spec/b_spec.rb"]),
+        )
+        .expect("valid patterns");
+
+        assert_eq!(
+            violations.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            vec![
+                "expected 3 splits (--total-splits) but the file has 2",
+                "./spec/gone_spec.rb in split 0 doesn't exist",
+                "spec/models in split 0 doesn't match any spec file",
+                "./spec/a_spec.rb is in splits 0, 1 and would run 2 times",
+                "./spec/b_spec.rb isn't in any split and would be added to the last split",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_accepts_valid_files() {
+        assert!(lint_buckets(
+            &buckets(r#"[[["./spec/a_spec.rb", 1]], ["spec/models"]]"#),
+            Some(2),
+            &paths(&["./spec/a_spec.rb", "./spec/models/b_spec.rb"]),
+        )
+        .unwrap()
+        .is_empty());
+        assert!(lint_buckets(
+            &buckets(r#"[[], {"files": [], "overflow": true}]"#),
+            Some(2),
+            &paths(&["./spec/a_spec.rb"]),
+        )
+        .unwrap()
+        .is_empty());
+    }
+}
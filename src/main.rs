@@ -1,25 +1,79 @@
 use std::collections::HashSet;
+use std::env;
 use std::fs::{self, File};
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use structopt::StructOpt;
 
+mod analysis;
+mod ci;
+mod codeowners;
+mod coverage;
+mod error;
+mod fingerprint;
+mod glob;
+mod html;
+mod lint;
+mod matrix;
+mod metrics;
 mod opt;
+mod output;
+mod plan;
+mod pre_bucketed;
+mod rebalance;
+mod report;
+mod runner;
 mod timings;
+mod trace;
 
-use crate::opt::Opt;
-use crate::timings::FileTiming;
+use crate::analysis::{Analysis, Sweep};
+use crate::ci::CiNode;
+use crate::codeowners::Codeowners;
+use crate::coverage::CoverageIndex;
+use crate::error::Error;
+use crate::matrix::{Matrix, MatrixEntry, MatrixProvider};
+use crate::opt::{CiOpt, Opt};
+use crate::output::{BucketManifest, EmptySplit, ManifestBucket, ReportFormat, SplitFile};
+use crate::plan::Plan;
+use crate::pre_bucketed::Bucket;
+use crate::timings::{get_file_stem, CostModel, FileTiming, Rollup, SuiteTotals};
 
-fn main() -> Result<(), failure::Error> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {}", err);
+        for cause in err.iter_causes() {
+            eprintln!("  caused by: {}", cause);
+        }
+
+        let exit_code = match err.downcast_ref::<Error>() {
+            Some(err) => {
+                if let Some(hint) = err.hint() {
+                    eprintln!("hint: {}", hint);
+                }
+                err.exit_code()
+            }
+            None => 1,
+        };
+        process::exit(exit_code);
+    }
+}
+
+fn run() -> Result<(), failure::Error> {
     let opt = Opt::from_args();
     match opt {
         Opt::Parse {
+            with_examples,
             rspec_file,
             output_file,
         } => {
-            let rspec_output = fs::read_to_string(rspec_file)?;
+            let rspec_output = read_input(rspec_file)?;
 
-            let file_timings = timings::parse_rspec_output(rspec_output)?;
+            let file_timings = if with_examples {
+                timings::parse_rspec_output_with_examples(rspec_output)?
+            } else {
+                timings::parse_rspec_output(rspec_output)?
+            };
             let timings_json = serde_json::to_string(&file_timings)?;
 
             let mut output_file = File::create(output_file)?;
@@ -28,162 +82,734 @@ fn main() -> Result<(), failure::Error> {
         Opt::SplitPreBucketed {
             total_splits,
             current_split,
+            ci,
+            strict,
+            empty,
             pre_bucketed_file,
         } => {
-            let pre_bucketed_file_clone = pre_bucketed_file.clone();
-            let bucketed_filenames: Vec<Vec<(String, Option<f64>)>> =
-                serde_json::from_str(&fs::read_to_string(pre_bucketed_file)?)?;
-
-            let current_split = current_split as usize;
-            if current_split >= bucketed_filenames.len() {
-                println!(
-                        "Error: when reading from pre-bucketed file: {:?} - current split should be between [0..{}), got {}.",
-                        pre_bucketed_file_clone, total_splits, current_split
-                    );
-                return Ok(());
+            let (total_splits, current_split) = resolve_splits(&ci, total_splits, current_split)?;
+            let current_split =
+                current_split.expect("--current-split is required without --ci") as usize;
+
+            let buckets: Vec<Bucket> = serde_json::from_str(&read_input(pre_bucketed_file)?)?;
+            error::check_split_index(current_split as u32, buckets.len() as u32)?;
+            let spec_paths = spec_paths()?;
+            let violations = lint::lint_buckets(&buckets, total_splits, &spec_paths)?;
+            for violation in &violations {
+                eprintln!("WARNING: {}", violation);
             }
+            if strict && !violations.is_empty() {
+                failure::bail!(
+                    "found {} problems in the pre-bucketed file, see 'lint'",
+                    violations.len()
+                );
+            }
+            let mut bucketed_filenames =
+                pre_bucketed::resolve_buckets(&buckets, &spec_paths)?.into_buckets();
+            let files = empty.empty_split(EmptySplit::Warn).guard(
+                bucketed_filenames.remove(current_split),
+                current_split as u32,
+            )?;
 
             println!(
                 "{}",
-                bucketed_filenames[current_split]
+                files
                     .iter()
                     .map(|(f, _)| f.to_owned())
                     .collect::<Vec<_>>()
                     .join(" ")
             );
 
-            if current_split == bucketed_filenames.len() - 1 {
-                let all_covered_file_paths = bucketed_filenames
+            return Ok(());
+        }
+        Opt::Lint {
+            total_splits,
+            pre_bucketed_file,
+        } => {
+            let buckets: Vec<Bucket> = serde_json::from_str(&read_input(pre_bucketed_file)?)?;
+            let violations = lint::lint_buckets(&buckets, total_splits, &spec_paths()?)?;
+            for violation in &violations {
+                println!("{}", violation);
+            }
+            if !violations.is_empty() {
+                failure::bail!(
+                    "found {} problems in the pre-bucketed file",
+                    violations.len()
+                );
+            }
+        }
+        Opt::Rebalance {
+            pinned,
+            max_imbalance,
+            output_file,
+            pre_bucketed_file,
+            timing_file,
+        } => {
+            let buckets: Vec<Bucket> = serde_json::from_str(&read_input(&pre_bucketed_file)?)?;
+            let file_timings = load_file_timings(&timing_file, &read_input(&timing_file)?)?;
+            let rebalance = rebalance::rebalance(
+                buckets,
+                &file_timings,
+                &spec_paths()?,
+                &pinned,
+                max_imbalance,
+            )?;
+            print!("{}", rebalance);
+
+            let output_file = output_file.unwrap_or(pre_bucketed_file);
+            let mut output_file = File::create(output_file)?;
+            // pretty-printed, since the file is maintained by hand
+            writeln!(
+                output_file,
+                "{}",
+                serde_json::to_string_pretty(&rebalance.buckets)?
+            )?;
+        }
+        Opt::Split {
+            total_splits,
+            current_split,
+            all,
+            out_dir,
+            plan_file,
+            expect_fingerprint,
+            processes_per_node,
+            cost,
+            empty,
+            format,
+            ci,
+            from_matrix,
+            timing_file,
+        } => {
+            let empty_split = empty.empty_split(EmptySplit::Warn);
+            if let Some(matrix_entry) = from_matrix {
+                let matrix_entry: MatrixEntry = serde_json::from_str(&matrix_entry)?;
+                let files = empty_split.guard(matrix_entry.files, matrix_entry.index)?;
+                print!("{}", output::format_files(&files, format));
+                return Ok(());
+            }
+
+            let (total_splits, current_split) = resolve_splits(&ci, total_splits, current_split)?;
+            let total_splits = total_splits.ok_or_else(|| {
+                failure::err_msg("--total-splits is required, the CI provider doesn't expose it")
+            })?;
+            let timing_file = timing_file.expect("timing file is required without --from-matrix");
+            let timing_output = read_input(&timing_file)?;
+            let file_timings = load_file_timings(&timing_file, &timing_output)?;
+            let cost_model = cost.cost_model();
+            let fingerprint = split_fingerprint(&timing_output, total_splits, &cost_model)?;
+
+            if let Some(expect_fingerprint) = expect_fingerprint {
+                plan::verify_fingerprint(
+                    &fingerprint,
+                    &expect_fingerprint,
+                    "--expect-fingerprint",
+                )?;
+            }
+
+            let mut bucketed_files = match plan_file {
+                Some(plan_file) => {
+                    let plan: Plan = serde_json::from_str(&read_input(plan_file)?)?;
+                    plan::verify_fingerprint(&fingerprint, &plan.fingerprint, "the plan")?;
+                    plan.buckets
+                }
+                None => bucketed_files(&file_timings, total_splits, &cost_model)?,
+            };
+
+            if all {
+                let out_dir = out_dir.expect("--all requires --out-dir");
+
+                // guard every bucket before writing any, so --on-empty exit leaves no
+                // bucket files behind
+                let manifest_buckets = bucketed_files
+                    .iter()
+                    .enumerate()
+                    .map(|(index, files)| {
+                        ManifestBucket::new(format!("bucket-{}.txt", index), files, &cost_model)
+                    })
+                    .collect::<Vec<_>>();
+                let bucketed_files = bucketed_files
                     .into_iter()
-                    .flat_map(|v| v.into_iter().map(|(f, _)| PathBuf::from(f)))
-                    .collect::<HashSet<_>>();
+                    .enumerate()
+                    .map(|(index, files)| empty_split.guard(files, index as u32))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                fs::create_dir_all(&out_dir)?;
+                for (manifest_bucket, files) in manifest_buckets.iter().zip(&bucketed_files) {
+                    let mut bucket_file = File::create(out_dir.join(&manifest_bucket.file))?;
+                    bucket_file.write_all(output::format_files(files, format).as_bytes())?;
+                }
 
-                print!(
-                    " {}",
-                    paths_not_covered(all_covered_file_paths)?
-                        .into_iter()
-                        .map(|p| p.to_str().unwrap().to_string())
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                )
+                let manifest = BucketManifest {
+                    fingerprint,
+                    total_splits,
+                    buckets: manifest_buckets,
+                };
+
+                let mut manifest_file = File::create(out_dir.join("manifest.json"))?;
+                manifest_file.write_all(serde_json::to_string(&manifest)?.as_bytes())?;
+                return Ok(());
             }
 
-            return Ok(());
+            let current_split =
+                current_split.expect("--current-split is required without --all or --ci");
+            error::check_split_index(current_split, total_splits)?;
+
+            if let Some(processes_per_node) = processes_per_node {
+                let processes = bucketed_files_per_node(
+                    &file_timings,
+                    total_splits,
+                    processes_per_node,
+                    &cost_model,
+                )?
+                .remove(current_split as usize)
+                .into_iter()
+                .map(|files| empty_split.guard(files, current_split))
+                .collect::<Result<Vec<_>, _>>()?;
+                print!("{}", output::format_processes(&processes, format)?);
+                return Ok(());
+            }
+
+            let files =
+                empty_split.guard(bucketed_files.remove(current_split as usize), current_split)?;
+            print!("{}", output::format_files(&files, format));
         }
-        Opt::Split {
+        Opt::Plan {
+            total_splits,
+            cost,
+            output_file,
+            timing_file,
+        } => {
+            let timing_output = read_input(&timing_file)?;
+            let file_timings = load_file_timings(&timing_file, &timing_output)?;
+
+            let cost_model = cost.cost_model();
+            let plan = Plan {
+                fingerprint: split_fingerprint(&timing_output, total_splits, &cost_model)?,
+                total_splits,
+                strategy: cost_model.strategy(),
+                cost_model,
+                buckets: bucketed_files(&file_timings, total_splits, &cost_model)?,
+            };
+
+            let mut output_file = File::create(output_file)?;
+            output_file.write_all(serde_json::to_string(&plan)?.as_bytes())?;
+            println!("{}", plan.fingerprint);
+        }
+        Opt::Matrix {
+            provider,
+            total_splits,
+            cost,
+            timing_file,
+        } => {
+            let timing_output = read_input(&timing_file)?;
+            let file_timings = load_file_timings(&timing_file, &timing_output)?;
+
+            let matrix = match provider {
+                MatrixProvider::Github => {
+                    let cost_model = cost.cost_model();
+                    Matrix::new(
+                        bucketed_files(&file_timings, total_splits, &cost_model)?,
+                        &cost_model,
+                    )
+                }
+            };
+            println!("{}", serde_json::to_string(&matrix)?);
+        }
+        Opt::Run {
             total_splits,
             current_split,
+            ci,
+            cost,
+            junit_output,
+            output_file,
             timing_file,
+            command,
         } => {
-            let timing_output = fs::read_to_string(timing_file)?;
-            let file_timings = load_file_timings(timing_output)?;
+            let (total_splits, current_split) = resolve_splits(&ci, total_splits, current_split)?;
+            let total_splits = total_splits.ok_or_else(|| {
+                failure::err_msg("--total-splits is required, the CI provider doesn't expose it")
+            })?;
+            let current_split = current_split.expect("--current-split is required without --ci");
+            error::check_split_index(current_split, total_splits)?;
+
+            let junit_output = junit_output
+                .unwrap_or_else(|| PathBuf::from(format!("rspec-results-{}.xml", current_split)));
+            let output_file = output_file
+                .unwrap_or_else(|| PathBuf::from(format!("rspec-parsed-{}.txt", current_split)));
+
+            let timing_output = read_input(&timing_file)?;
+            let file_timings = load_file_timings(&timing_file, &timing_output)?;
+            let files = bucketed_files(&file_timings, total_splits, &cost.cost_model())?
+                .remove(current_split as usize);
 
-            if current_split >= total_splits {
-                println!(
-                    "Error: current split should be between [0..{}), got {}.",
-                    total_splits, current_split
+            // running the command without any files would run the entire suite
+            if files.is_empty() {
+                eprintln!(
+                    "Split {} has no spec files, skipping '{}'.",
+                    current_split,
+                    command.join(" ")
                 );
+                let mut output_file = File::create(output_file)?;
+                output_file.write_all(b"[]")?;
                 return Ok(());
             }
 
-            let bucket = {
-                let mut bucketed_timings = timings::split_timings(&file_timings, total_splits);
-                bucketed_timings.remove(current_split as usize)
-            };
+            runner::remove_stale_output(&junit_output)?;
+            let status = runner::rspec_command(&command, &junit_output, &files).status()?;
 
-            print!(
-                "{}",
-                bucket
-                    .into_iter()
-                    .map(|t| t.file_path)
-                    .collect::<Vec<_>>()
-                    .join(" ")
-            );
+            match fs::read_to_string(&junit_output) {
+                Ok(rspec_output) => {
+                    let file_timings = timings::parse_rspec_output(rspec_output)?;
+                    let timings_json = serde_json::to_string(&file_timings)?;
 
-            if current_split == total_splits - 1 {
-                print!(
-                    " {}",
-                    paths_not_covered_by_timings(&file_timings)?
-                        .into_iter()
-                        .map(|p| p.to_str().unwrap().to_string())
-                        .collect::<Vec<_>>()
-                        .join(" ")
-                )
+                    let mut output_file = File::create(output_file)?;
+                    output_file.write_all(timings_json.as_bytes())?;
+                }
+                Err(err) => eprintln!(
+                    "WARNING: Could not read JUnit output {:?} ({}), no timings were written!",
+                    junit_output, err
+                ),
             }
 
-            println!("");
+            if !status.success() {
+                process::exit(runner::exit_code(status));
+            }
+        }
+        Opt::RunLocal {
+            jobs,
+            first_is_1,
+            cost,
+            output_file,
+            in_place,
+            timing_file,
+            command,
+        } => {
+            let timing_output = read_input(&timing_file)?;
+            let file_timings = load_file_timings(&timing_file, &timing_output)?;
+            let cost_model = cost.cost_model();
+
+            let mut running_jobs = vec![];
+            for (index, files) in bucketed_files(&file_timings, jobs, &cost_model)?
+                .into_iter()
+                .enumerate()
+            {
+                // running the command without any files would run the entire suite
+                if files.is_empty() {
+                    continue;
+                }
+
+                let junit_output = PathBuf::from(format!("rspec-results-local-{}.xml", index));
+                let mut cmd = runner::rspec_command(&command, &junit_output, &files);
+                cmd.env(
+                    "TEST_ENV_NUMBER",
+                    runner::test_env_number(index, first_is_1),
+                );
+
+                let started = runner::remove_stale_output(&junit_output)
+                    .and_then(|_| runner::spawn_prefixed(cmd, format!("[{}] ", index + 1)));
+                match started {
+                    Ok(child) => running_jobs.push((index, files, junit_output, child)),
+                    Err(err) => {
+                        // don't leave the jobs started so far running on their own
+                        for (_, _, _, child) in running_jobs {
+                            child.kill();
+                        }
+                        return Err(err.into());
+                    }
+                }
+            }
+            let running_jobs = running_jobs
+                .into_iter()
+                .map(|(index, files, junit_output, child)| {
+                    (index, files, junit_output, child.wait_in_background())
+                })
+                .collect::<Vec<_>>();
+
+            let mut exit_code = 0;
+            let mut newer_timings = vec![];
+            let mut summary = vec![];
+            for (index, files, junit_output, handle) in running_jobs {
+                let (status, elapsed) = handle.join().expect("job thread panicked")?;
+                if !status.success() && exit_code == 0 {
+                    exit_code = runner::exit_code(status);
+                }
+
+                match fs::read_to_string(&junit_output) {
+                    Ok(rspec_output) => {
+                        newer_timings.append(&mut timings::parse_rspec_output(rspec_output)?)
+                    }
+                    Err(err) => eprintln!(
+                        "WARNING: Could not read JUnit output {:?} ({}), its timings are not updated!",
+                        junit_output, err
+                    ),
+                }
+
+                let predicted_time = output::predicted_time(&files, &cost_model);
+                summary.push(format!(
+                    "[JOB {} - predicted {:.2}s, took {:.2}s] {} files, {}",
+                    index + 1,
+                    predicted_time,
+                    elapsed.as_secs_f64(),
+                    files.len(),
+                    if status.success() {
+                        "passed".to_string()
+                    } else {
+                        format!("failed ({})", status)
+                    }
+                ));
+            }
+
+            println!();
+            for line in summary {
+                println!("{}", line);
+            }
+
+            let timings_json =
+                serde_json::to_string(&timings::merge_timings(&file_timings, &newer_timings))?;
+            let output_file = if in_place {
+                timing_file
+            } else {
+                output_file.expect("--output is required without --in-place")
+            };
+            let mut output_file = File::create(output_file)?;
+            output_file.write_all(timings_json.as_bytes())?;
+
+            if exit_code != 0 {
+                process::exit(exit_code);
+            }
         }
         Opt::Analyze {
             total_splits,
+            rollup,
+            depth,
+            codeowners_file,
+            cost,
+            format,
+            html_file,
+            trace_file,
             output_file,
             timing_file,
         } => {
-            let mut non_covered_paths = 0;
+            let timing_output = read_input(&timing_file)?;
+            let file_timings = load_file_timings(&timing_file, &timing_output)?;
 
-            let timing_output = fs::read_to_string(timing_file)?;
-            let file_timings = load_file_timings(timing_output)?;
-            let bucketed_timings = timings::split_timings(&file_timings, total_splits);
-            let mut bucketed_filenames = vec![];
+            let report = match rollup {
+                Some(Rollup::Dir) => Some(timings::rollup_timings(&file_timings, |path| {
+                    Some(timings::directory_prefix(path, depth))
+                })),
+                Some(Rollup::Codeowners) => {
+                    let codeowners_file = match codeowners_file {
+                        Some(codeowners_file) => codeowners_file,
+                        None => Codeowners::find(Path::new(".")).ok_or_else(|| {
+                            failure::err_msg(
+                                "no CODEOWNERS file found in .github/, the root or docs/, \
+                                 pass --codeowners instead",
+                            )
+                        })?,
+                    };
+                    let codeowners = Codeowners::parse(&read_input(codeowners_file)?)?;
+                    Some(timings::rollup_timings(&file_timings, |path| {
+                        let owners = codeowners.owners(path);
+                        if owners.is_empty() {
+                            None
+                        } else {
+                            Some(owners.join(" "))
+                        }
+                    }))
+                }
+                None => None,
+            };
+            if let Some(report) = report {
+                match format {
+                    ReportFormat::Text => print!("{}", report),
+                    ReportFormat::Json => println!("{}", serde_json::to_string(&report)?),
+                    ReportFormat::Markdown => print!("{}", report.markdown()),
+                }
+                return Ok(());
+            }
 
-            for (index, bucket) in bucketed_timings.into_iter().enumerate() {
-                let bucket_total_time: f64 = bucket.iter().map(|t| t.total_time).sum();
-                let mut full_file_names = bucket
-                    .iter()
-                    .map(|t| (t.file_path.to_string(), Some(t.total_time)))
-                    .collect::<Vec<_>>();
-                let mut file_names = bucket
-                    .into_iter()
-                    .map(|t| {
-                        let file_stem = get_file_stem(&PathBuf::from(t.file_path));
-                        format!("{}:{:.2}s", file_stem, t.total_time)
-                    })
-                    .collect::<Vec<_>>();
+            let split_range = total_splits.expect("required unless --rollup");
+            let cost_model = cost.cost_model();
+            let total_splits = match split_range.single() {
+                Some(total_splits) => total_splits,
+                None => {
+                    if html_file.is_some() || trace_file.is_some() || output_file.is_some() {
+                        failure::bail!(
+                            "--html, --trace and --output need a single number of --total-splits"
+                        );
+                    }
 
-                if index + 1 == total_splits as usize {
-                    let paths_not_covered_by_timings = paths_not_covered_by_timings(&file_timings)?;
-                    if !paths_not_covered_by_timings.is_empty() {
-                        non_covered_paths = paths_not_covered_by_timings.len();
+                    let sweep = Sweep::new(split_range, |total_splits| {
+                        Ok(Analysis::new(
+                            bucketed_files(&file_timings, total_splits, &cost_model)?,
+                            0.0,
+                            &cost_model,
+                        ))
+                    })?;
+                    match format {
+                        ReportFormat::Text => print!("{}", sweep),
+                        ReportFormat::Json => println!("{}", serde_json::to_string(&sweep)?),
+                        ReportFormat::Markdown => print!("{}", sweep.markdown()),
                     }
-                    full_file_names.append(
-                        &mut paths_not_covered_by_timings
-                            .iter()
-                            .map(|p| (p.to_str().unwrap().to_string(), None))
-                            .collect::<Vec<_>>(),
-                    );
-                    file_names.append(
-                        &mut paths_not_covered_by_timings
-                            .into_iter()
-                            .map(|p| format!("{}:NA", get_file_stem(&p)))
-                            .collect::<Vec<_>>(),
-                    );
+                    return Ok(());
                 }
-                println!(
-                    "[BUCKET {} - {:.2}s] {}",
-                    index + 1,
-                    bucket_total_time,
-                    file_names.join(", ")
-                );
+            };
+            let analysis = Analysis::new(
+                bucketed_files(&file_timings, total_splits, &cost_model)?,
+                file_timings.iter().map(|t| t.unattributed_time).sum(),
+                &cost_model,
+            );
+            match format {
+                ReportFormat::Text => print!("{}", analysis),
+                ReportFormat::Json => println!("{}", serde_json::to_string(&analysis)?),
+                ReportFormat::Markdown => print!("{}", analysis.markdown()),
+            }
 
-                bucketed_filenames.push(full_file_names)
+            if let Some(trace_file) = trace_file {
+                let trace = trace::predicted_trace(&analysis, &file_timings, &cost_model);
+                File::create(trace_file)?.write_all(serde_json::to_string(&trace)?.as_bytes())?;
             }
 
-            if non_covered_paths > 0 {
-                println!(
-                    "WARNING: Found {} non-covered paths, please re-run split timing script to fix!",
-                    non_covered_paths
-                )
+            if let Some(html_file) = html_file {
+                File::create(html_file)?.write_all(html::analysis_html(&analysis).as_bytes())?;
             }
 
             if let Some(output_file) = output_file {
+                let bucketed_filenames = analysis
+                    .buckets
+                    .iter()
+                    .map(|b| &b.files)
+                    .collect::<Vec<_>>();
                 let bucketed_filenames = serde_json::to_string(&bucketed_filenames)?;
 
                 let mut output_file = File::create(output_file)?;
                 output_file.write_all(bucketed_filenames.as_bytes())?;
             }
         }
+        Opt::Report {
+            top,
+            format,
+            plan_file,
+            junit_files,
+        } => {
+            let plan: Plan = serde_json::from_str(&read_input(plan_file)?)?;
+            let mut junit_timings = vec![];
+            for junit_file in junit_files {
+                let timings = timings::parse_rspec_output(read_input(&junit_file)?)?;
+                junit_timings.push((junit_file.display().to_string(), timings));
+            }
+
+            let report = report::run_report(&plan, &junit_timings, top)?;
+            match format {
+                ReportFormat::Text => print!("{}", report),
+                ReportFormat::Json => println!("{}", serde_json::to_string(&report)?),
+                ReportFormat::Markdown => print!("{}", report.markdown()),
+            }
+        }
+        Opt::Diff {
+            fail_if_slower,
+            format,
+            old_timing_file,
+            new_timing_file,
+        } => {
+            // Compares the timing files as is, files deleted since aren't filtered out
+            let old_timings = parse_timing_file(&old_timing_file, &read_input(&old_timing_file)?)?;
+            let new_timings = parse_timing_file(&new_timing_file, &read_input(&new_timing_file)?)?;
+            let diff = timings::diff_timings(&old_timings, &new_timings);
+
+            match format {
+                ReportFormat::Text => print!("{}", diff),
+                ReportFormat::Json => println!("{}", serde_json::to_string(&diff)?),
+                ReportFormat::Markdown => print!("{}", diff.markdown()),
+            }
+
+            if let (Some(threshold), Some(percent)) = (fail_if_slower, diff.percent) {
+                if percent > threshold.0 {
+                    failure::bail!(
+                        "suite got {:.1}% slower, more than the allowed {}%",
+                        percent,
+                        threshold.0
+                    );
+                }
+            }
+        }
+        Opt::Metrics {
+            total_splits,
+            cost,
+            top,
+            output_file,
+            timing_file,
+        } => {
+            let timing_output = read_input(&timing_file)?;
+            let file_timings = load_file_timings(&timing_file, &timing_output)?;
+            let timing_file_age = fs::metadata(&timing_file)?
+                .modified()
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+
+            let cost_model = cost.cost_model();
+            let analysis = Analysis::new(
+                bucketed_files(&file_timings, total_splits, &cost_model)?,
+                file_timings.iter().map(|t| t.unattributed_time).sum(),
+                &cost_model,
+            );
+            let metrics = metrics::suite_metrics(&analysis, &file_timings, timing_file_age, top);
+
+            match output_file {
+                Some(output_file) => {
+                    // the textfile collector may read the file at any time, so it's
+                    // written next to it and moved into place
+                    let mut tmp_file = output_file.clone().into_os_string();
+                    tmp_file.push(".tmp");
+                    File::create(&tmp_file)?.write_all(metrics.as_bytes())?;
+                    fs::rename(&tmp_file, output_file)?;
+                }
+                None => print!("{}", metrics),
+            }
+        }
+        Opt::IndexCoverage {
+            timing_file,
+            root,
+            resultset_files,
+        } => {
+            let root = match root {
+                Some(root) => root,
+                None => env::current_dir()?,
+            };
+
+            let mut index = CoverageIndex::default();
+            for resultset_file in resultset_files {
+                index.add_resultset(read_input(resultset_file)?, &root)?;
+            }
+            let index_json = serde_json::to_string(&index)?;
+
+            let mut output_file = File::create(coverage::index_path_for(&timing_file))?;
+            output_file.write_all(index_json.as_bytes())?;
+        }
+        Opt::SplitImpacted {
+            total_splits,
+            current_split,
+            changed_files,
+            root,
+            ignore_unindexed,
+            empty,
+            timing_file,
+        } => {
+            let root = match root {
+                Some(root) => root,
+                None => env::current_dir()?,
+            };
+
+            let timing_output = read_input(&timing_file)?;
+            let file_timings = load_file_timings(&timing_file, &timing_output)?;
+            let index: CoverageIndex =
+                serde_json::from_str(&read_input(coverage::index_path_for(&timing_file))?)?;
+
+            error::check_split_index(current_split, total_splits)?;
+
+            let changed_files = if changed_files == Path::new("-") {
+                let mut changed_files = String::new();
+                io::stdin().read_to_string(&mut changed_files)?;
+                changed_files
+            } else {
+                read_input(changed_files)?
+            };
+            let changed_files = changed_files
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect::<Vec<_>>();
+
+            let unindexed_files = index.unindexed_files(&changed_files, &root);
+            for unindexed_file in &unindexed_files {
+                eprintln!("WARNING: {} isn't in the coverage index", unindexed_file);
+            }
+
+            let files = if !unindexed_files.is_empty() && !ignore_unindexed {
+                // the specs impacted by a file outside of the index are unknown
+                eprintln!(
+                    "WARNING: {} changed files aren't in the coverage index, outputting the \
+                     full split instead (see --ignore-unindexed)",
+                    unindexed_files.len()
+                );
+                bucketed_files(&file_timings, total_splits, &CostModel::default())?
+                    .remove(current_split as usize)
+            } else {
+                let impacted_specs = index.impacted_specs(&changed_files, &root);
+                let impacted_timings = file_timings
+                    .into_iter()
+                    .filter(|t| impacted_specs.contains(&t.file_path))
+                    .collect::<Vec<_>>();
+
+                let bucket = {
+                    let mut bucketed_timings =
+                        timings::split_timings(&impacted_timings, total_splits);
+                    bucketed_timings.remove(current_split as usize)
+                };
+                let mut files = bucket
+                    .into_iter()
+                    .map(|t| (t.file_path, Some(t.total_time)))
+                    .collect::<Vec<_>>();
+
+                // impacted specs without timing information go into the last split
+                if current_split == total_splits - 1 {
+                    files.extend(
+                        paths_not_covered_by_timings(&impacted_timings)?
+                            .into_iter()
+                            .map(|p| p.to_str().unwrap().to_string())
+                            .filter(|p| impacted_specs.contains(p))
+                            .map(|p| (p, None)),
+                    );
+                }
+                files
+            };
+
+            // no impacted specs (ex. a docs-only change) must not run the entire suite
+            let files = empty
+                .empty_split(EmptySplit::Exit)
+                .guard(files, current_split)?;
+            println!(
+                "{}",
+                files
+                    .into_iter()
+                    .map(|(f, _)| f)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+        }
+        Opt::EstimateOverhead { rspec_files } => {
+            let mut suite_totals = SuiteTotals {
+                suite_time: 0.0,
+                example_time: 0.0,
+                attributed_time: 0.0,
+                file_count: 0,
+            };
+            for rspec_file in rspec_files {
+                let file_totals = timings::parse_suite_totals(read_input(rspec_file)?)?;
+                suite_totals.suite_time += file_totals.suite_time;
+                suite_totals.example_time += file_totals.example_time;
+                suite_totals.attributed_time += file_totals.attributed_time;
+                suite_totals.file_count += file_totals.file_count;
+            }
+
+            println!(
+                "Suite time: {:.2}s, example time: {:.2}s, in the file times from 'parse': \
+                 {:.2}s, unattributed: {:.2}s across {} files",
+                suite_totals.suite_time,
+                suite_totals.example_time,
+                suite_totals.attributed_time,
+                suite_totals.unattributed_time(),
+                suite_totals.file_count
+            );
+            println!(
+                "Suggested per-file overhead: --file-overhead {:.2}",
+                suite_totals.file_overhead()
+            );
+        }
         Opt::OutputMissing { timing_file } => {
-            let timing_output = fs::read_to_string(timing_file)?;
-            let file_timings = load_file_timings(timing_output)?;
+            let timing_output = read_input(&timing_file)?;
+            let file_timings = load_file_timings(&timing_file, &timing_output)?;
             let paths_not_covered_by_timings = paths_not_covered_by_timings(&file_timings)?;
             for path in paths_not_covered_by_timings {
                 println!("{}", path.to_str().unwrap());
@@ -194,8 +820,26 @@ fn main() -> Result<(), failure::Error> {
     Ok(())
 }
 
-fn load_file_timings(timing_output: String) -> Result<Vec<FileTiming>, failure::Error> {
-    let mut file_timings: Vec<FileTiming> = serde_json::from_str(&timing_output)?;
+/// Reads an input file of the command line.
+fn read_input(path: impl AsRef<Path>) -> Result<String, Error> {
+    fs::read_to_string(&path).map_err(|cause| Error::UnreadableInput {
+        path: path.as_ref().to_path_buf(),
+        cause,
+    })
+}
+
+fn parse_timing_file(timing_file: &Path, timing_output: &str) -> Result<Vec<FileTiming>, Error> {
+    serde_json::from_str(timing_output).map_err(|cause| Error::MalformedTimingFile {
+        path: timing_file.to_path_buf(),
+        cause,
+    })
+}
+
+fn load_file_timings(
+    timing_file: &Path,
+    timing_output: &str,
+) -> Result<Vec<FileTiming>, failure::Error> {
+    let mut file_timings = parse_timing_file(timing_file, timing_output)?;
     let spec_paths = read_specs_recursively()?
         .into_iter()
         .collect::<HashSet<_>>();
@@ -203,6 +847,116 @@ fn load_file_timings(timing_output: String) -> Result<Vec<FileTiming>, failure::
     Ok(file_timings)
 }
 
+/// Resolves the total and current split, filling in the ones not passed on the
+/// command line from the CI environment when --ci is given.
+fn resolve_splits(
+    ci: &CiOpt,
+    total_splits: Option<u32>,
+    current_split: Option<u32>,
+) -> Result<(Option<u32>, Option<u32>), failure::Error> {
+    let provider = match ci.ci {
+        Some(provider) => provider,
+        None => return Ok((total_splits, current_split)),
+    };
+
+    let node = CiNode::from_env(provider, |name| env::var(name).ok())?;
+    if ci.verbose {
+        eprintln!("{}", node);
+    }
+
+    Ok((
+        total_splits.or(node.total_splits),
+        current_split.or(Some(node.current_split)),
+    ))
+}
+
+/// Fingerprints the inputs of a split as seen from the current directory.
+fn split_fingerprint(
+    timing_output: &str,
+    total_splits: u32,
+    cost_model: &CostModel,
+) -> Result<String, failure::Error> {
+    let spec_paths = read_specs_recursively()?
+        .into_iter()
+        .map(|p| p.to_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+
+    Ok(fingerprint::split_fingerprint(
+        timing_output,
+        &spec_paths,
+        total_splits,
+        &cost_model.strategy(),
+    ))
+}
+
+/// Splits the timings into buckets of (file path, predicted time), adding the spec
+/// files not covered by the timings to the last bucket without a predicted time.
+fn bucketed_files(
+    file_timings: &[FileTiming],
+    total_splits: u32,
+    cost_model: &CostModel,
+) -> Result<Vec<Vec<SplitFile>>, failure::Error> {
+    let mut bucketed_files =
+        timings::split_timings_with_cost(file_timings, total_splits, cost_model)
+            .into_iter()
+            .map(|bucket| {
+                bucket
+                    .into_iter()
+                    .map(|t| (t.file_path, Some(t.total_time)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+    if let Some(last_bucket) = bucketed_files.last_mut() {
+        last_bucket.extend(
+            paths_not_covered_by_timings(file_timings)?
+                .into_iter()
+                .map(|p| (p.to_str().unwrap().to_string(), None)),
+        );
+    }
+
+    Ok(bucketed_files)
+}
+
+/// Splits the timings into the buckets of every process on every node, adding the
+/// spec files not covered by the timings to the last process of the last node.
+fn bucketed_files_per_node(
+    file_timings: &[FileTiming],
+    total_splits: u32,
+    processes_per_node: u32,
+    cost_model: &CostModel,
+) -> Result<Vec<Vec<Vec<SplitFile>>>, failure::Error> {
+    let mut bucketed_files = timings::split_timings_per_node(
+        file_timings,
+        total_splits,
+        processes_per_node,
+        cost_model,
+    )?
+    .into_iter()
+    .map(|processes| {
+        processes
+            .into_iter()
+            .map(|bucket| {
+                bucket
+                    .into_iter()
+                    .map(|t| (t.file_path, Some(t.total_time)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    })
+    .collect::<Vec<_>>();
+
+    if let Some(last_bucket) = bucketed_files.last_mut().and_then(|p| p.last_mut()) {
+        last_bucket.extend(
+            paths_not_covered_by_timings(file_timings)?
+                .into_iter()
+                .map(|p| (p.to_str().unwrap().to_string(), None)),
+        );
+    }
+
+    Ok(bucketed_files)
+}
+
 fn paths_not_covered_by_timings(timings: &[FileTiming]) -> Result<Vec<PathBuf>, failure::Error> {
     let covered_paths = timings
         .iter()
@@ -220,12 +974,31 @@ fn paths_not_covered(covered_paths: HashSet<PathBuf>) -> Result<Vec<PathBuf>, fa
         }
     }
 
-    Ok(not_covered_paths.into_iter().collect())
+    // sorted so that every run outputs the uncovered paths in the same order
+    let mut not_covered_paths = not_covered_paths.into_iter().collect::<Vec<_>>();
+    not_covered_paths.sort();
+    Ok(not_covered_paths)
+}
+
+/// Paths of the spec files in ./spec in sorted order.
+fn spec_paths() -> Result<Vec<String>, failure::Error> {
+    let mut spec_paths = read_specs_recursively()?
+        .into_iter()
+        .map(|p| p.to_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    spec_paths.sort();
+    Ok(spec_paths)
 }
 
 fn read_specs_recursively() -> Result<Vec<PathBuf>, failure::Error> {
     let mut specs = vec![];
-    let mut dirs_to_read = vec![fs::read_dir("./spec")?];
+    let spec_dir = Path::new("./spec");
+    let mut dirs_to_read = vec![
+        fs::read_dir(spec_dir).map_err(|cause| Error::MissingSpecDir {
+            path: spec_dir.to_path_buf(),
+            cause,
+        })?,
+    ];
     while let Some(dir) = dirs_to_read.pop() {
         for entry in dir {
             let entry = entry?;
@@ -242,7 +1015,3 @@ fn read_specs_recursively() -> Result<Vec<PathBuf>, failure::Error> {
     }
     Ok(specs)
 }
-
-fn get_file_stem(path: &PathBuf) -> String {
-    path.file_stem().unwrap().to_str().unwrap().to_string()
-}
@@ -0,0 +1,90 @@
+use crate::output::{self, SplitFile};
+use crate::timings::CostModel;
+use serde_derive::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// CI providers a job matrix can be generated for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatrixProvider {
+    Github,
+}
+
+impl MatrixProvider {
+    pub fn variants() -> &'static [&'static str] {
+        &["github"]
+    }
+}
+
+impl FromStr for MatrixProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(MatrixProvider::Github),
+            _ => Err(format!(
+                "unknown provider '{}', expected one of: {}",
+                s,
+                MatrixProvider::variants().join(", ")
+            )),
+        }
+    }
+}
+
+/// CI job matrix with one entry per split, in the format of GitHub Actions' `matrix.include`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Matrix {
+    pub include: Vec<MatrixEntry>,
+}
+
+/// A single job of the matrix, which reads its files back with 'split --from-matrix'.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatrixEntry {
+    pub index: u32,
+    pub files: Vec<SplitFile>,
+    pub predicted_seconds: f64,
+}
+
+impl Matrix {
+    pub fn new(bucketed_files: Vec<Vec<SplitFile>>, cost_model: &CostModel) -> Matrix {
+        Matrix {
+            include: bucketed_files
+                .into_iter()
+                .enumerate()
+                .map(|(index, files)| MatrixEntry {
+                    index: index as u32,
+                    predicted_seconds: output::predicted_time(&files, cost_model),
+                    files,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_serializes_a_github_matrix() {
+        let matrix = Matrix::new(
+            vec![
+                vec![("./spec/a_spec.rb".to_string(), Some(2.0))],
+                vec![
+                    ("./spec/b_spec.rb".to_string(), Some(1.5)),
+                    ("./spec/c_spec.rb".to_string(), None),
+                ],
+            ],
+            &CostModel::default(),
+        );
+
+        let matrix_json = serde_json::to_string(&matrix).unwrap();
+        assert_eq!(
+            matrix_json,
+            r#"{"include":[{"index":0,"files":[["./spec/a_spec.rb",2.0]],"predicted_seconds":2.0},{"index":1,"files":[["./spec/b_spec.rb",1.5],["./spec/c_spec.rb",null]],"predicted_seconds":1.5}]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Matrix>(&matrix_json).unwrap(),
+            matrix
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use crate::analysis::Analysis;
+use crate::timings::FileTiming;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Writes gauges of the suite timings in the OpenMetrics text format, which is also
+/// read by node_exporter's textfile collector.
+struct MetricsWriter {
+    output: String,
+}
+
+impl MetricsWriter {
+    fn new() -> MetricsWriter {
+        MetricsWriter {
+            output: String::new(),
+        }
+    }
+
+    /// Starts a gauge, `name` is suffixed with its unit as OpenMetrics expects.
+    fn gauge(&mut self, name: &str, unit: Option<&str>, help: &str) -> &mut MetricsWriter {
+        writeln!(self.output, "# TYPE {} gauge", name).unwrap();
+        if let Some(unit) = unit {
+            writeln!(self.output, "# UNIT {} {}", name, unit).unwrap();
+        }
+        writeln!(self.output, "# HELP {} {}", name, help).unwrap();
+        self
+    }
+
+    /// Adds a sample to the last gauge.
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) -> &mut MetricsWriter {
+        self.output.push_str(name);
+        if !labels.is_empty() {
+            let labels = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+                .collect::<Vec<_>>();
+            write!(self.output, "{{{}}}", labels.join(",")).unwrap();
+        }
+        writeln!(self.output, " {}", format_value(value)).unwrap();
+        self
+    }
+
+    fn finish(mut self) -> String {
+        self.output.push_str("# EOF\n");
+        self.output
+    }
+}
+
+/// Metrics of the analysis, the timing file's age (if known) and its `top` slowest files.
+pub fn suite_metrics(
+    analysis: &Analysis,
+    file_timings: &[FileTiming],
+    timing_file_age: Option<Duration>,
+    top: usize,
+) -> String {
+    let stats = &analysis.stats;
+    let mut metrics = MetricsWriter::new();
+    metrics
+        .gauge(
+            "rspec_timing_suite_seconds",
+            Some("seconds"),
+            "Predicted time of the whole suite across all buckets.",
+        )
+        .sample("rspec_timing_suite_seconds", &[], stats.total_time);
+
+    metrics.gauge(
+        "rspec_timing_bucket_predicted_seconds",
+        Some("seconds"),
+        "Predicted time of each bucket.",
+    );
+    for (index, bucket) in analysis.buckets.iter().enumerate() {
+        metrics.sample(
+            "rspec_timing_bucket_predicted_seconds",
+            &[("bucket", &(index + 1).to_string())],
+            bucket.predicted_time,
+        );
+    }
+
+    metrics
+        .gauge(
+            "rspec_timing_imbalance_ratio",
+            None,
+            "Ratio of the slowest to the fastest bucket, +Inf if a bucket is empty.",
+        )
+        .sample(
+            "rspec_timing_imbalance_ratio",
+            &[],
+            stats.max_min_ratio.unwrap_or(f64::INFINITY),
+        )
+        .gauge(
+            "rspec_timing_efficiency_ratio",
+            None,
+            "Share of the buckets' time spent running specs instead of waiting on the slowest bucket.",
+        )
+        .sample("rspec_timing_efficiency_ratio", &[], stats.efficiency)
+        .gauge(
+            "rspec_timing_uncovered_specs",
+            None,
+            "Number of spec files without timing information.",
+        )
+        .sample(
+            "rspec_timing_uncovered_specs",
+            &[],
+            analysis.uncovered_count as f64,
+        );
+
+    if let Some(timing_file_age) = timing_file_age {
+        metrics
+            .gauge(
+                "rspec_timing_file_age_seconds",
+                Some("seconds"),
+                "Time since the timing file was last modified.",
+            )
+            .sample(
+                "rspec_timing_file_age_seconds",
+                &[],
+                timing_file_age.as_secs_f64().round(),
+            );
+    }
+
+    let mut slowest_files = file_timings.iter().collect::<Vec<_>>();
+    slowest_files.sort_by(|a, b| b.total_time.partial_cmp(&a.total_time).unwrap());
+    metrics.gauge(
+        "rspec_timing_spec_file_seconds",
+        Some("seconds"),
+        &format!("Time of the {} slowest spec files.", top),
+    );
+    for file_timing in slowest_files.into_iter().take(top) {
+        metrics.sample(
+            "rspec_timing_spec_file_seconds",
+            &[("file", &file_timing.file_path)],
+            file_timing.total_time,
+        );
+    }
+
+    metrics.finish()
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timings::ft;
+    use crate::timings::CostModel;
+
+    #[test]
+    fn it_writes_openmetrics_gauges() {
+        let file_timings = vec![ft("./spec/a_spec.rb", 3.0), ft("./spec/\"b\"_spec.rb", 1.5)];
+        let analysis = Analysis::new(
+            vec![
+                vec![("./spec/a_spec.rb".to_string(), Some(3.0))],
+                vec![
+                    ("./spec/\"b\"_spec.rb".to_string(), Some(1.5)),
+                    ("./spec/c_spec.rb".to_string(), None),
+                ],
+                vec![],
+            ],
+            0.0,
+            &CostModel::default(),
+        );
+
+        assert_eq!(
+            suite_metrics(&analysis, &file_timings, Some(Duration::from_secs(90)), 1),
+            "# TYPE rspec_timing_suite_seconds gauge\n\
+             # UNIT rspec_timing_suite_seconds seconds\n\
+             # HELP rspec_timing_suite_seconds Predicted time of the whole suite across all buckets.\n\
+             rspec_timing_suite_seconds 4.5\n\
+             # TYPE rspec_timing_bucket_predicted_seconds gauge\n\
+             # UNIT rspec_timing_bucket_predicted_seconds seconds\n\
+             # HELP rspec_timing_bucket_predicted_seconds Predicted time of each bucket.\n\
+             rspec_timing_bucket_predicted_seconds{bucket=\"1\"} 3\n\
+             rspec_timing_bucket_predicted_seconds{bucket=\"2\"} 1.5\n\
+             rspec_timing_bucket_predicted_seconds{bucket=\"3\"} 0\n\
+             # TYPE rspec_timing_imbalance_ratio gauge\n\
+             # HELP rspec_timing_imbalance_ratio Ratio of the slowest to the fastest bucket, +Inf if a bucket is empty.\n\
+             rspec_timing_imbalance_ratio +Inf\n\
+             # TYPE rspec_timing_efficiency_ratio gauge\n\
+             # HELP rspec_timing_efficiency_ratio Share of the buckets' time spent running specs instead of waiting on the slowest bucket.\n\
+             rspec_timing_efficiency_ratio 0.5\n\
+             # TYPE rspec_timing_uncovered_specs gauge\n\
+             # HELP rspec_timing_uncovered_specs Number of spec files without timing information.\n\
+             rspec_timing_uncovered_specs 1\n\
+             # TYPE rspec_timing_file_age_seconds gauge\n\
+             # UNIT rspec_timing_file_age_seconds seconds\n\
+             # HELP rspec_timing_file_age_seconds Time since the timing file was last modified.\n\
+             rspec_timing_file_age_seconds 90\n\
+             # TYPE rspec_timing_spec_file_seconds gauge\n\
+             # UNIT rspec_timing_spec_file_seconds seconds\n\
+             # HELP rspec_timing_spec_file_seconds Time of the 1 slowest spec files.\n\
+             rspec_timing_spec_file_seconds{file=\"./spec/a_spec.rb\"} 3\n\
+             # EOF\n"
+        );
+        assert_eq!(escape_label("a\"b\\c\n"), "a\\\"b\\\\c\\n");
+    }
+}
@@ -1,6 +1,109 @@
+use crate::analysis::SplitRange;
+use crate::ci::Ci;
+use crate::matrix::MatrixProvider;
+use crate::output::{EmptySplit, OnEmpty, OutputFormat, ReportFormat};
+use crate::timings::{CostModel, Percent, Rollup};
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+/// Parses a count that must be at least 1, ex. --total-splits or --jobs.
+fn parse_count(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(count) => Ok(count),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Parses a number of seconds that can't be negative, ex. --node-overhead.
+fn parse_overhead(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(seconds) if seconds >= 0.0 => Ok(seconds),
+        Ok(_) => Err("must not be negative".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Options of the fixed costs included when balancing and predicting splits.
+#[derive(Debug, StructOpt)]
+pub struct CostOpt {
+    /// Seconds each node spends before running any spec file, ex. booting
+    /// Rails or loading the schema
+    #[structopt(
+        long = "node-overhead",
+        default_value = "0",
+        parse(try_from_str = "parse_overhead")
+    )]
+    node_overhead: f64,
+
+    /// Seconds each spec file costs on top of its timing, ex. loading the
+    /// file (see 'estimate-overhead')
+    #[structopt(
+        long = "file-overhead",
+        default_value = "0",
+        parse(try_from_str = "parse_overhead")
+    )]
+    file_overhead: f64,
+}
+
+impl CostOpt {
+    pub fn cost_model(&self) -> CostModel {
+        CostModel {
+            node_overhead: self.node_overhead,
+            file_overhead: self.file_overhead,
+        }
+    }
+}
+
+/// Options of reading the total and current split from the CI environment.
+#[derive(Debug, StructOpt)]
+pub struct CiOpt {
+    /// Read the total and current split from the CI environment: auto,
+    /// circleci, gitlab, buildkite, semaphore, travis or kubernetes
+    #[structopt(long = "ci", raw(possible_values = "Ci::variants()"))]
+    pub ci: Option<Ci>,
+
+    /// Log the detected CI provider to stderr
+    #[structopt(short = "v", long = "verbose")]
+    pub verbose: bool,
+}
+
+/// Options of what the split subcommands print for a split without spec files.
+#[derive(Debug, StructOpt)]
+pub struct EmptyOpt {
+    /// What to do for a split without spec files, which rspec would run the entire suite
+    /// for: print the --empty-sentinel (sentinel), exit with code 7 (exit) or print the
+    /// --noop-arg (noop). Without it 'split-impacted' exits with code 7, the other
+    /// subcommands print nothing and log a warning to stderr
+    #[structopt(long = "on-empty", raw(possible_values = "OnEmpty::variants()"))]
+    on_empty: Option<OnEmpty>,
+
+    /// Printed for a split without spec files with --on-empty sentinel
+    #[structopt(long = "empty-sentinel", default_value = "__EMPTY_SPLIT__")]
+    empty_sentinel: String,
+
+    /// Printed for a split without spec files with --on-empty noop, ex. the path of a
+    /// spec file without examples
+    #[structopt(long = "noop-arg", raw(required_if = r#""on_empty", "noop""#))]
+    noop_arg: Option<String>,
+}
+
+impl EmptyOpt {
+    /// What to print for a split without spec files, `default` without --on-empty.
+    pub fn empty_split(&self, default: EmptySplit) -> EmptySplit {
+        match self.on_empty {
+            None => default,
+            Some(OnEmpty::Sentinel) => EmptySplit::Replace(self.empty_sentinel.clone()),
+            Some(OnEmpty::Exit) => EmptySplit::Exit,
+            Some(OnEmpty::Noop) => EmptySplit::Replace(
+                self.noop_arg
+                    .clone()
+                    .expect("--noop-arg is required with --on-empty noop"),
+            ),
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "rspec-timing-tool",
@@ -9,6 +112,11 @@ use structopt::StructOpt;
 pub enum Opt {
     #[structopt(name = "parse")]
     Parse {
+        /// Keep the time of every example in the output, ex. to show them in
+        /// 'analyze --trace'
+        #[structopt(long = "with-examples")]
+        with_examples: bool,
+
         /// Input file of rspec timing information
         /// Ex. 'bundle exec rspec --format RspecJunitFormatter -o rspec-results.xml'
         /// Then rspec_file is: rspec-results.xml
@@ -22,39 +130,390 @@ pub enum Opt {
     #[structopt(name = "split-pre-bucketed")]
     SplitPreBucketed {
         /// Number of total splits the timing data is split into
-        #[structopt(short = "s", long = "total-splits")]
-        total_splits: u32,
+        #[structopt(
+            short = "s",
+            long = "total-splits",
+            parse(try_from_str = "parse_count"),
+            raw(required_unless = r#""ci""#)
+        )]
+        total_splits: Option<u32>,
 
         /// Current split needed to output files
-        #[structopt(short = "c", long = "current-split")]
-        current_split: u32,
+        #[structopt(short = "c", long = "current-split", raw(required_unless = r#""ci""#))]
+        current_split: Option<u32>,
+
+        #[structopt(flatten)]
+        ci: CiOpt,
+
+        /// Refuse to split if 'lint' finds any problem with the pre-bucketed file,
+        /// the problems are only logged to stderr otherwise
+        #[structopt(long = "strict")]
+        strict: bool,
+
+        #[structopt(flatten)]
+        empty: EmptyOpt,
 
         /// Read splits from pre-bucketed file, useful for
         /// manual balancing.
         #[structopt(parse(from_os_str))]
         pre_bucketed_file: PathBuf,
     },
+    #[structopt(name = "lint")]
+    Lint {
+        /// Number of total splits the pre-bucketed file should have
+        #[structopt(
+            short = "s",
+            long = "total-splits",
+            parse(try_from_str = "parse_count")
+        )]
+        total_splits: Option<u32>,
+
+        /// Pre-bucketed file to check, see 'split-pre-bucketed'
+        #[structopt(parse(from_os_str))]
+        pre_bucketed_file: PathBuf,
+    },
+    #[structopt(name = "rebalance")]
+    Rebalance {
+        /// Bucket (0-based, like --current-split) that new spec files aren't
+        /// added to, can be repeated
+        #[structopt(long = "pin", number_of_values = 1)]
+        pinned: Vec<usize>,
+
+        /// Move files between the buckets that aren't pinned until the slowest
+        /// bucket takes at most this much longer than the average bucket, ex. '10%'
+        #[structopt(long = "max-imbalance")]
+        max_imbalance: Option<Percent>,
+
+        /// Output file of the rebalanced buckets, defaults to updating the
+        /// pre-bucketed file
+        #[structopt(parse(from_os_str), short = "o", long = "output")]
+        output_file: Option<PathBuf>,
+
+        /// Pre-bucketed file to rebalance, see 'split-pre-bucketed'
+        #[structopt(parse(from_os_str))]
+        pre_bucketed_file: PathBuf,
+
+        /// Input file of parsed timing information
+        #[structopt(parse(from_os_str))]
+        timing_file: PathBuf,
+    },
     #[structopt(name = "split")]
     Split {
         /// Number of total splits the timing data is split into
-        #[structopt(short = "s", long = "total-splits")]
-        total_splits: u32,
+        #[structopt(
+            short = "s",
+            long = "total-splits",
+            parse(try_from_str = "parse_count"),
+            raw(required_unless_one = r#"&["from_matrix", "ci"]"#)
+        )]
+        total_splits: Option<u32>,
 
         /// Current split needed to output files
-        #[structopt(short = "c", long = "current-split")]
-        current_split: u32,
+        #[structopt(
+            short = "c",
+            long = "current-split",
+            raw(
+                required_unless_one = r#"&["all", "from_matrix", "ci"]"#,
+                conflicts_with = r#""all""#
+            )
+        )]
+        current_split: Option<u32>,
+
+        #[structopt(flatten)]
+        ci: CiOpt,
+
+        /// Write every split into --out-dir as bucket-<split>.txt, along
+        /// with a manifest.json
+        #[structopt(long = "all", raw(requires = r#""out_dir""#))]
+        all: bool,
+
+        /// Directory the splits are written to with --all
+        #[structopt(parse(from_os_str), long = "out-dir")]
+        out_dir: Option<PathBuf>,
+
+        /// Read the splits from a plan instead of computing them, refusing
+        /// to split if this node's inputs differ from the plan's
+        #[structopt(parse(from_os_str), long = "plan")]
+        plan_file: Option<PathBuf>,
+
+        /// Refuse to split if this node's inputs don't have this fingerprint
+        #[structopt(long = "expect-fingerprint")]
+        expect_fingerprint: Option<String>,
+
+        /// Split each node's files between this many processes running on the
+        /// node, printing one line per process (or a JSON array per process)
+        #[structopt(
+            long = "processes-per-node",
+            parse(try_from_str = "parse_count"),
+            raw(conflicts_with_all = r#"&["all", "plan_file", "expect_fingerprint"]"#)
+        )]
+        processes_per_node: Option<u32>,
+
+        #[structopt(flatten)]
+        cost: CostOpt,
+
+        #[structopt(flatten)]
+        empty: EmptyOpt,
+
+        /// How the files are printed: space, newline, nul, json or shell (quoted)
+        #[structopt(
+            long = "format",
+            default_value = "space",
+            raw(possible_values = "OutputFormat::variants()")
+        )]
+        format: OutputFormat,
+
+        /// Output the files of a job generated by 'matrix', ex. '${{ toJson(matrix) }}'
+        #[structopt(
+            long = "from-matrix",
+            raw(conflicts_with_all = r#"&["all", "plan_file", "expect_fingerprint"]"#)
+        )]
+        from_matrix: Option<String>,
+
+        /// Input file of parsed timing information
+        #[structopt(parse(from_os_str), raw(required_unless = r#""from_matrix""#))]
+        timing_file: Option<PathBuf>,
+    },
+    #[structopt(name = "plan")]
+    Plan {
+        /// Number of total splits the timing data is split into
+        #[structopt(
+            short = "s",
+            long = "total-splits",
+            parse(try_from_str = "parse_count")
+        )]
+        total_splits: u32,
+
+        #[structopt(flatten)]
+        cost: CostOpt,
+
+        /// Output file of the plan, the fingerprint is printed to stdout
+        #[structopt(parse(from_os_str), short = "o", long = "output")]
+        output_file: PathBuf,
+
+        /// Input file of parsed timing information
+        #[structopt(parse(from_os_str))]
+        timing_file: PathBuf,
+    },
+    #[structopt(name = "matrix")]
+    Matrix {
+        /// CI provider to generate the job matrix for
+        #[structopt(
+            long = "provider",
+            default_value = "github",
+            raw(possible_values = "MatrixProvider::variants()")
+        )]
+        provider: MatrixProvider,
+
+        /// Number of total splits the timing data is split into
+        #[structopt(
+            short = "s",
+            long = "total-splits",
+            parse(try_from_str = "parse_count")
+        )]
+        total_splits: u32,
+
+        #[structopt(flatten)]
+        cost: CostOpt,
 
         /// Input file of parsed timing information
         #[structopt(parse(from_os_str))]
         timing_file: PathBuf,
     },
+    #[structopt(name = "run")]
+    Run {
+        /// Number of total splits the timing data is split into
+        #[structopt(
+            short = "s",
+            long = "total-splits",
+            parse(try_from_str = "parse_count"),
+            raw(required_unless = r#""ci""#)
+        )]
+        total_splits: Option<u32>,
+
+        /// Current split to run the files of
+        #[structopt(short = "c", long = "current-split", raw(required_unless = r#""ci""#))]
+        current_split: Option<u32>,
+
+        #[structopt(flatten)]
+        ci: CiOpt,
+
+        #[structopt(flatten)]
+        cost: CostOpt,
+
+        /// JUnit output file the command is forced to write to, defaults to
+        /// rspec-results-<current split>.xml
+        #[structopt(parse(from_os_str), long = "junit-output")]
+        junit_output: Option<PathBuf>,
+
+        /// Output file of the timing information parsed from this split's run,
+        /// defaults to rspec-parsed-<current split>.txt
+        #[structopt(parse(from_os_str), short = "o", long = "output")]
+        output_file: Option<PathBuf>,
+
+        /// Input file of parsed timing information
+        #[structopt(parse(from_os_str))]
+        timing_file: PathBuf,
+
+        /// Command to run with the spec files appended
+        /// Ex. 'rspec-timing-tool run -s 4 -c 0 rspec-parsed.txt -- bundle exec rspec'
+        #[structopt(raw(last = "true", required = "true"))]
+        command: Vec<String>,
+    },
+    #[structopt(name = "run-local")]
+    RunLocal {
+        /// Number of processes to split the timing data into and run at once
+        #[structopt(short = "j", long = "jobs", parse(try_from_str = "parse_count"))]
+        jobs: u32,
+
+        /// Set TEST_ENV_NUMBER to 1 for the first process instead of leaving
+        /// it empty, like parallel_tests' --first-is-1
+        #[structopt(long = "first-is-1")]
+        first_is_1: bool,
+
+        #[structopt(flatten)]
+        cost: CostOpt,
+
+        /// Output file of the updated timing information
+        #[structopt(
+            parse(from_os_str),
+            short = "o",
+            long = "output",
+            raw(required_unless = r#""in_place""#)
+        )]
+        output_file: Option<PathBuf>,
+
+        /// Write the updated timing information to the input file instead of --output
+        #[structopt(long = "in-place", raw(conflicts_with = r#""output_file""#))]
+        in_place: bool,
+
+        /// Input file of parsed timing information
+        #[structopt(parse(from_os_str))]
+        timing_file: PathBuf,
+
+        /// Command to run with the spec files appended
+        /// Ex. 'rspec-timing-tool run-local -j 8 rspec-parsed.txt -- bundle exec rspec'
+        #[structopt(raw(last = "true", required = "true"))]
+        command: Vec<String>,
+    },
     #[structopt(name = "analyze")]
     Analyze {
+        /// Number of total splits the timing data is split into, or a range
+        /// (ex. '2..16') to compare the balance of every number of splits in it
+        #[structopt(
+            short = "s",
+            long = "total-splits",
+            raw(required_unless = r#""rollup""#)
+        )]
+        total_splits: Option<SplitRange>,
+
+        /// Show the suite time per group instead of the splits: dir (directory)
+        /// or codeowners (owners in CODEOWNERS)
+        #[structopt(long = "rollup", raw(possible_values = "Rollup::variants()"))]
+        rollup: Option<Rollup>,
+
+        /// Number of directories kept when rolling up by directory
+        #[structopt(long = "depth", default_value = "2")]
+        depth: usize,
+
+        /// CODEOWNERS file used when rolling up by owner, defaults to the one
+        /// in .github/, the root or docs/
+        #[structopt(parse(from_os_str), long = "codeowners")]
+        codeowners_file: Option<PathBuf>,
+
+        #[structopt(flatten)]
+        cost: CostOpt,
+
+        /// How the analysis is printed: text, json or markdown
+        #[structopt(
+            long = "format",
+            default_value = "text",
+            raw(possible_values = "ReportFormat::variants()")
+        )]
+        format: ReportFormat,
+
+        /// Write a self-contained HTML report of the buckets, ex. to attach as a
+        /// CI artifact
+        #[structopt(parse(from_os_str), long = "html")]
+        html_file: Option<PathBuf>,
+
+        /// Write the predicted schedule of every bucket in the Chrome Trace Event
+        /// format, ex. to open in chrome://tracing or Perfetto
+        #[structopt(parse(from_os_str), long = "trace")]
+        trace_file: Option<PathBuf>,
+
+        /// Dump buckets into an output file
+        #[structopt(parse(from_os_str), short = "o", long = "output")]
+        output_file: Option<PathBuf>,
+
+        /// Input file of parsed timing information
+        #[structopt(parse(from_os_str))]
+        timing_file: PathBuf,
+    },
+    #[structopt(name = "report")]
+    Report {
+        /// Number of files that deviated most from their predicted time to show
+        #[structopt(long = "top", default_value = "10")]
+        top: usize,
+
+        /// How the report is printed: text, json or markdown
+        #[structopt(
+            long = "format",
+            default_value = "text",
+            raw(possible_values = "ReportFormat::variants()")
+        )]
+        format: ReportFormat,
+
+        /// Plan the nodes were split with, see 'plan'
+        #[structopt(parse(from_os_str), long = "plan")]
+        plan_file: PathBuf,
+
+        /// JUnit files written by the nodes, in any order
+        /// Ex. 'node-*/rspec-results.xml'
+        #[structopt(parse(from_os_str), raw(required = "true"))]
+        junit_files: Vec<PathBuf>,
+    },
+    #[structopt(name = "diff")]
+    Diff {
+        /// Fail if the total suite time got slower by more than this, ex. '20%'
+        #[structopt(long = "fail-if-slower")]
+        fail_if_slower: Option<Percent>,
+
+        /// How the changes are printed: text, json or markdown
+        #[structopt(
+            long = "format",
+            default_value = "text",
+            raw(possible_values = "ReportFormat::variants()")
+        )]
+        format: ReportFormat,
+
+        /// Input file of parsed timing information to compare against
+        #[structopt(parse(from_os_str))]
+        old_timing_file: PathBuf,
+
+        /// Input file of parsed timing information to compare
+        #[structopt(parse(from_os_str))]
+        new_timing_file: PathBuf,
+    },
+    #[structopt(name = "metrics")]
+    Metrics {
         /// Number of total splits the timing data is split into
-        #[structopt(short = "s", long = "total-splits")]
+        #[structopt(
+            short = "s",
+            long = "total-splits",
+            parse(try_from_str = "parse_count")
+        )]
         total_splits: u32,
 
-        /// Dump buckets into an output file
+        #[structopt(flatten)]
+        cost: CostOpt,
+
+        /// Number of slowest spec files exported
+        #[structopt(long = "top", default_value = "10")]
+        top: usize,
+
+        /// Output file of the metrics, ex. in node_exporter's textfile collector
+        /// directory, defaults to stdout
         #[structopt(parse(from_os_str), short = "o", long = "output")]
         output_file: Option<PathBuf>,
 
@@ -62,6 +521,68 @@ pub enum Opt {
         #[structopt(parse(from_os_str))]
         timing_file: PathBuf,
     },
+    #[structopt(name = "index-coverage")]
+    IndexCoverage {
+        /// Input file of parsed timing information, the coverage index
+        /// is written next to it
+        #[structopt(parse(from_os_str), short = "t", long = "timing-file")]
+        timing_file: PathBuf,
+
+        /// Root the SimpleCov result sets were recorded in, defaults to
+        /// the current directory
+        #[structopt(parse(from_os_str), long = "root")]
+        root: Option<PathBuf>,
+
+        /// SimpleCov result sets (.resultset.json), recorded per spec file
+        /// Ex. 'SimpleCov.command_name(example.file_path)'
+        #[structopt(parse(from_os_str), raw(required = "true"))]
+        resultset_files: Vec<PathBuf>,
+    },
+    #[structopt(name = "split-impacted")]
+    SplitImpacted {
+        /// Number of total splits the timing data is split into
+        #[structopt(
+            short = "s",
+            long = "total-splits",
+            parse(try_from_str = "parse_count")
+        )]
+        total_splits: u32,
+
+        /// Current split needed to output files
+        #[structopt(short = "c", long = "current-split")]
+        current_split: u32,
+
+        /// File listing the changed files, one per line ('-' for stdin)
+        /// Ex. 'git diff --name-only origin/master'
+        #[structopt(parse(from_os_str), long = "changed-files")]
+        changed_files: PathBuf,
+
+        /// Root the changed files are relative to, defaults to the
+        /// current directory
+        #[structopt(parse(from_os_str), long = "root")]
+        root: Option<PathBuf>,
+
+        /// Only output the specs impacted by the changed files in the coverage index,
+        /// instead of the full split when a changed file isn't in it (ex. a new file,
+        /// config/ or spec/spec_helper.rb)
+        #[structopt(long = "ignore-unindexed")]
+        ignore_unindexed: bool,
+
+        #[structopt(flatten)]
+        empty: EmptyOpt,
+
+        /// Input file of parsed timing information, the coverage index
+        /// is read from next to it
+        #[structopt(parse(from_os_str))]
+        timing_file: PathBuf,
+    },
+    #[structopt(name = "estimate-overhead")]
+    EstimateOverhead {
+        /// Input files of rspec timing information, ex. one per node
+        /// Ex. 'bundle exec rspec --format RspecJunitFormatter -o rspec-results.xml'
+        #[structopt(parse(from_os_str), raw(required = "true"))]
+        rspec_files: Vec<PathBuf>,
+    },
     #[structopt(name = "output-missing")]
     OutputMissing {
         /// Input file of parsed timing information
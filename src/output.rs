@@ -0,0 +1,383 @@
+use crate::error::Error;
+use crate::timings::CostModel;
+use serde_derive::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// A file path in a split along with its predicted time, or None if the file
+/// is not covered by the timing information.
+pub type SplitFile = (String, Option<f64>);
+
+/// How the file paths of a split are printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Space,
+    Newline,
+    Nul,
+    Json,
+    Shell,
+}
+
+impl OutputFormat {
+    pub fn variants() -> &'static [&'static str] {
+        &["space", "newline", "nul", "json", "shell"]
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "space" => Ok(OutputFormat::Space),
+            "newline" => Ok(OutputFormat::Newline),
+            "nul" => Ok(OutputFormat::Nul),
+            "json" => Ok(OutputFormat::Json),
+            "shell" => Ok(OutputFormat::Shell),
+            _ => Err(format!(
+                "unknown format '{}', expected one of: {}",
+                s,
+                OutputFormat::variants().join(", ")
+            )),
+        }
+    }
+}
+
+/// What 'split' does for a split without spec files with --on-empty, as rspec runs
+/// the entire suite when it gets no files.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnEmpty {
+    /// Print --empty-sentinel for the CI script to check
+    Sentinel,
+    /// Exit with a dedicated exit code
+    Exit,
+    /// Print --noop-arg, an argument that makes rspec run nothing
+    Noop,
+}
+
+impl OnEmpty {
+    pub fn variants() -> &'static [&'static str] {
+        &["sentinel", "exit", "noop"]
+    }
+}
+
+impl FromStr for OnEmpty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sentinel" => Ok(OnEmpty::Sentinel),
+            "exit" => Ok(OnEmpty::Exit),
+            "noop" => Ok(OnEmpty::Noop),
+            _ => Err(format!(
+                "unknown policy '{}', expected one of: {}",
+                s,
+                OnEmpty::variants().join(", ")
+            )),
+        }
+    }
+}
+
+/// What 'split' prints in place of an empty split, see `OnEmpty`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmptySplit {
+    /// Print nothing, warning on stderr
+    Warn,
+    /// Print the argument instead
+    Replace(String),
+    /// Fail with `Error::EmptySplit`
+    Exit,
+}
+
+impl EmptySplit {
+    /// Files printed for the split, which are the split's files unless it has none.
+    pub fn guard(
+        &self,
+        files: Vec<SplitFile>,
+        current_split: u32,
+    ) -> Result<Vec<SplitFile>, Error> {
+        if !files.is_empty() {
+            return Ok(files);
+        }
+
+        match self {
+            EmptySplit::Warn => {
+                eprintln!(
+                    "WARNING: split {} has no spec files, rspec runs the entire suite without any (see --on-empty)",
+                    current_split
+                );
+                Ok(files)
+            }
+            EmptySplit::Replace(argument) => Ok(vec![(argument.clone(), None)]),
+            EmptySplit::Exit => Err(Error::EmptySplit { current_split }),
+        }
+    }
+}
+
+/// How reports about timing information are printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    /// GitHub-flavored Markdown, ex. for pull request comments
+    Markdown,
+}
+
+impl ReportFormat {
+    pub fn variants() -> &'static [&'static str] {
+        &["text", "json", "markdown"]
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "markdown" => Ok(ReportFormat::Markdown),
+            _ => Err(format!(
+                "unknown format '{}', expected one of: {}",
+                s,
+                ReportFormat::variants().join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum FileStatus {
+    /// Predicted time comes from the timing file
+    Estimated,
+    /// File has no timing information
+    Uncovered,
+}
+
+#[derive(Debug, Serialize)]
+struct FileEntry<'a> {
+    file_path: &'a str,
+    predicted_time: Option<f64>,
+    status: FileStatus,
+}
+
+/// Manifest written alongside the bucket files of 'split --all'.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct BucketManifest {
+    /// Fingerprint of the inputs the buckets were computed from
+    pub fingerprint: String,
+    pub total_splits: u32,
+    pub buckets: Vec<ManifestBucket>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManifestBucket {
+    /// File name of the bucket, relative to the manifest
+    pub file: String,
+    pub predicted_time: f64,
+    pub file_count: usize,
+    pub uncovered_count: usize,
+    /// Whether the bucket has no spec files, its file then has what --on-empty prints
+    #[serde(default)]
+    pub empty: bool,
+}
+
+impl ManifestBucket {
+    pub fn new(file: String, files: &[SplitFile], cost_model: &CostModel) -> ManifestBucket {
+        ManifestBucket {
+            file,
+            predicted_time: predicted_time(files, cost_model),
+            file_count: files.len(),
+            uncovered_count: files.iter().filter(|(_, t)| t.is_none()).count(),
+            empty: files.is_empty(),
+        }
+    }
+}
+
+/// Predicted time of running the files under the cost model, where files without
+/// timing information only count their overhead.
+pub fn predicted_time(files: &[SplitFile], cost_model: &CostModel) -> f64 {
+    if files.is_empty() {
+        return 0.0;
+    }
+
+    cost_model.node_overhead
+        + files
+            .iter()
+            .map(|(_, t)| t.unwrap_or(0.0) + cost_model.file_overhead)
+            .sum::<f64>()
+}
+
+/// Formats the files in a split.
+///
+/// The output is terminated (newline / NUL) so it can be printed as is.
+pub fn format_files(files: &[SplitFile], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Space => {
+            let mut output = files
+                .iter()
+                .map(|(f, _)| f.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            output.push('\n');
+            output
+        }
+        OutputFormat::Newline => files.iter().map(|(f, _)| format!("{}\n", f)).collect(),
+        OutputFormat::Nul => files.iter().map(|(f, _)| format!("{}\0", f)).collect(),
+        OutputFormat::Json => {
+            let mut output = serde_json::to_string(&file_entries(files))
+                .expect("file entries are always serializable");
+            output.push('\n');
+            output
+        }
+        OutputFormat::Shell => {
+            let mut output = files
+                .iter()
+                .map(|(f, _)| shell_quote(f))
+                .collect::<Vec<_>>()
+                .join(" ");
+            output.push('\n');
+            output
+        }
+    }
+}
+
+/// Formats the files of every process on a node, one line per process or a JSON
+/// array with an entry per process.
+pub fn format_processes(
+    processes: &[Vec<SplitFile>],
+    format: OutputFormat,
+) -> Result<String, failure::Error> {
+    match format {
+        OutputFormat::Space | OutputFormat::Shell => Ok(processes
+            .iter()
+            .map(|files| format_files(files, format))
+            .collect()),
+        OutputFormat::Json => {
+            let entries = processes
+                .iter()
+                .map(|files| file_entries(files))
+                .collect::<Vec<_>>();
+            Ok(format!("{}\n", serde_json::to_string(&entries)?))
+        }
+        OutputFormat::Newline | OutputFormat::Nul => failure::bail!(
+            "files of multiple processes can only be printed in the space, shell or json format"
+        ),
+    }
+}
+
+fn file_entries(files: &[SplitFile]) -> Vec<FileEntry<'_>> {
+    files
+        .iter()
+        .map(|(file_path, predicted_time)| FileEntry {
+            file_path,
+            predicted_time: *predicted_time,
+            status: match predicted_time {
+                Some(_) => FileStatus::Estimated,
+                None => FileStatus::Uncovered,
+            },
+        })
+        .collect()
+}
+
+/// Formats a time in seconds, or '-' when there is none.
+pub fn format_time(time: Option<f64>) -> String {
+    match time {
+        Some(time) => format!("{:.2}s", time),
+        None => "-".to_string(),
+    }
+}
+
+/// Formats a signed change in time along with its percentage when there is one.
+pub fn format_delta(delta: f64, percent: Option<f64>) -> String {
+    match percent {
+        Some(percent) => format!("{:+.2}s, {:+.1}%", delta, percent),
+        None => format!("{:+.2}s", delta),
+    }
+}
+
+/// Formats the text as inline code that can be used in a Markdown table cell.
+pub fn markdown_code(text: &str) -> String {
+    let text = text.replace('|', "\\|");
+    if text.contains('`') {
+        format!("`` {} ``", text)
+    } else {
+        format!("`{}`", text)
+    }
+}
+
+/// Quotes the argument for POSIX shells, leaving it as is when there is nothing to quote.
+pub fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=+@%,".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg.to_string();
+    }
+
+    format!("'{}'", arg.replace('\'', r#"'\''"#))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Vec<SplitFile> {
+        vec![
+            ("./spec/models/app_spec.rb".to_string(), Some(1.5)),
+            ("./spec/it's a [glob]_spec.rb".to_string(), None),
+        ]
+    }
+
+    #[test]
+    fn it_formats_plain_separators() {
+        assert_eq!(
+            format_files(&files(), OutputFormat::Space),
+            "./spec/models/app_spec.rb ./spec/it's a [glob]_spec.rb\n"
+        );
+        assert_eq!(
+            format_files(&files(), OutputFormat::Newline),
+            "./spec/models/app_spec.rb\n./spec/it's a [glob]_spec.rb\n"
+        );
+        assert_eq!(
+            format_files(&files(), OutputFormat::Nul),
+            "./spec/models/app_spec.rb\0./spec/it's a [glob]_spec.rb\0"
+        );
+        assert_eq!(format_files(&[], OutputFormat::Newline), "");
+    }
+
+    #[test]
+    fn it_quotes_for_shells() {
+        assert_eq!(
+            format_files(&files(), OutputFormat::Shell),
+            r#"./spec/models/app_spec.rb './spec/it'\''s a [glob]_spec.rb'"#.to_string() + "\n"
+        );
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn it_formats_json_with_status() {
+        assert_eq!(
+            format_files(&files(), OutputFormat::Json),
+            r#"[{"file_path":"./spec/models/app_spec.rb","predicted_time":1.5,"status":"estimated"},{"file_path":"./spec/it's a [glob]_spec.rb","predicted_time":null,"status":"uncovered"}]"#.to_string() + "\n"
+        );
+    }
+
+    #[test]
+    fn it_formats_markdown_code() {
+        assert_eq!(markdown_code("./spec/a_spec.rb"), "`./spec/a_spec.rb`");
+        assert_eq!(markdown_code("./spec/a|`b`.rb"), r"`` ./spec/a\|`b`.rb ``");
+    }
+
+    #[test]
+    fn it_formats_one_line_per_process() {
+        let processes = vec![
+            files(),
+            vec![],
+            vec![("./spec/b_spec.rb".to_string(), Some(2.0))],
+        ];
+        assert_eq!(
+            format_processes(&processes, OutputFormat::Shell).unwrap(),
+            "./spec/models/app_spec.rb './spec/it'\\''s a [glob]_spec.rb'\n\n./spec/b_spec.rb\n"
+        );
+        assert!(format_processes(&processes, OutputFormat::Newline).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::output::SplitFile;
+use crate::timings::CostModel;
+use serde_derive::{Deserialize, Serialize};
+
+/// Complete assignment of spec files to splits, along with a fingerprint of the
+/// inputs it was computed from.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub fingerprint: String,
+    pub total_splits: u32,
+    pub strategy: String,
+    /// Fixed costs the buckets were balanced with, none for plans written before
+    /// they were recorded
+    #[serde(default)]
+    pub cost_model: CostModel,
+    pub buckets: Vec<Vec<SplitFile>>,
+}
+
+/// Errors if the fingerprint of this node's inputs differs from the fingerprint
+/// expected by `expected_by` (ex. the plan), as splitting would then produce
+/// overlapping or missing coverage across nodes.
+pub fn verify_fingerprint(
+    fingerprint: &str,
+    expected_fingerprint: &str,
+    expected_by: &str,
+) -> Result<(), failure::Error> {
+    if fingerprint != expected_fingerprint {
+        failure::bail!(
+            "inputs have fingerprint {} but {} expects {}, the timing file, spec files, \
+             total splits or strategy differ from the ones the split was planned with",
+            fingerprint,
+            expected_by,
+            expected_fingerprint
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_rejects_mismatched_fingerprints() {
+        assert!(verify_fingerprint("0123", "0123", "the plan").is_ok());
+
+        let err = verify_fingerprint("0123", "4567", "the plan").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("inputs have fingerprint 0123 but the plan expects 4567"));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::glob::Glob;
+use crate::output::SplitFile;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Entry of a bucket in a pre-bucketed file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BucketEntry {
+    /// Spec file with its time (if known), ex. `["./spec/a_spec.rb", 1.5]`
+    File(SplitFile),
+    /// Glob or directory matching any number of spec files, ex. `"spec/features/**"`
+    Pattern(String),
+}
+
+/// Bucket of a pre-bucketed file, either a list of entries or an object with the entries
+/// in `files` that can be marked as an overflow bucket:
+/// `{"files": ["spec/features/**"], "overflow": true}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "BucketRepr", into = "BucketRepr")]
+pub struct Bucket {
+    pub entries: Vec<BucketEntry>,
+    /// Whether spec files that aren't in any bucket go to this bucket
+    pub overflow: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum BucketRepr {
+    Entries(Vec<BucketEntry>),
+    Object {
+        files: Vec<BucketEntry>,
+        #[serde(default)]
+        overflow: bool,
+    },
+}
+
+impl From<BucketRepr> for Bucket {
+    fn from(repr: BucketRepr) -> Bucket {
+        match repr {
+            BucketRepr::Entries(entries) => Bucket {
+                entries,
+                overflow: false,
+            },
+            BucketRepr::Object { files, overflow } => Bucket {
+                entries: files,
+                overflow,
+            },
+        }
+    }
+}
+
+impl From<Bucket> for BucketRepr {
+    fn from(bucket: Bucket) -> BucketRepr {
+        // plain lists keep files written by 'analyze -o' unchanged
+        if bucket.overflow {
+            BucketRepr::Object {
+                files: bucket.entries,
+                overflow: true,
+            }
+        } else {
+            BucketRepr::Entries(bucket.entries)
+        }
+    }
+}
+
+impl Bucket {
+    /// Spec files listed explicitly in the bucket.
+    pub fn files(&self) -> impl Iterator<Item = &SplitFile> {
+        self.entries.iter().filter_map(|entry| match entry {
+            BucketEntry::File(file) => Some(file),
+            BucketEntry::Pattern(_) => None,
+        })
+    }
+
+    /// Globs and directories of the bucket.
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().filter_map(|entry| match entry {
+            BucketEntry::File(_) => None,
+            BucketEntry::Pattern(pattern) => Some(pattern.as_str()),
+        })
+    }
+}
+
+/// Spec files of every bucket of a pre-bucketed file.
+#[derive(Debug, PartialEq)]
+pub struct Resolution {
+    /// Files of each bucket, the explicitly listed files first and then the spec files
+    /// matched by its patterns in path order
+    pub buckets: Vec<Vec<SplitFile>>,
+    /// Spec files matched by each pattern of each bucket, in the order of `Bucket::patterns`
+    pub pattern_matches: Vec<Vec<usize>>,
+    /// Spec files that aren't in any bucket, in path order
+    pub leftovers: Vec<String>,
+    /// Indexes of the buckets marked as overflow
+    pub overflow: Vec<usize>,
+}
+
+/// Resolves the buckets against the spec files: explicitly listed files stay where they
+/// are, and every other spec file goes to the first bucket with a glob or directory
+/// matching it.
+pub fn resolve_buckets(
+    buckets: &[Bucket],
+    spec_paths: &[String],
+) -> Result<Resolution, failure::Error> {
+    let globs = buckets
+        .iter()
+        .map(|bucket| {
+            bucket
+                .patterns()
+                .map(Glob::new)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let listed_paths = buckets
+        .iter()
+        .flat_map(|bucket| bucket.files().map(|(f, _)| f.as_str()))
+        .collect::<HashSet<_>>();
+
+    let mut resolved = buckets
+        .iter()
+        .map(|bucket| bucket.files().cloned().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut pattern_matches = globs
+        .iter()
+        .map(|globs| vec![0; globs.len()])
+        .collect::<Vec<_>>();
+    let mut leftovers = vec![];
+    for spec_path in spec_paths {
+        if listed_paths.contains(spec_path.as_str()) {
+            continue;
+        }
+
+        let matching = globs.iter().enumerate().find_map(|(index, globs)| {
+            globs
+                .iter()
+                .position(|glob| glob.is_match(spec_path))
+                .map(|pattern| (index, pattern))
+        });
+        match matching {
+            Some((index, pattern)) => {
+                pattern_matches[index][pattern] += 1;
+                resolved[index].push((spec_path.clone(), None));
+            }
+            None => leftovers.push(spec_path.clone()),
+        }
+    }
+
+    Ok(Resolution {
+        buckets: resolved,
+        pattern_matches,
+        leftovers,
+        overflow: buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, bucket)| bucket.overflow)
+            .map(|(index, _)| index)
+            .collect(),
+    })
+}
+
+impl Resolution {
+    /// Files of every bucket with the leftover spec files dealt round-robin to the overflow
+    /// buckets, or added to the last bucket if no bucket is marked as overflow.
+    pub fn into_buckets(self) -> Vec<Vec<SplitFile>> {
+        let mut buckets = self.buckets;
+        let overflow = if self.overflow.is_empty() {
+            buckets.len().checked_sub(1).into_iter().collect()
+        } else {
+            self.overflow
+        };
+        if overflow.is_empty() {
+            return buckets;
+        }
+
+        for (index, leftover) in self.leftovers.into_iter().enumerate() {
+            buckets[overflow[index % overflow.len()]].push((leftover, None));
+        }
+        buckets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn bucket_files(resolution: Resolution) -> Vec<Vec<String>> {
+        resolution
+            .into_buckets()
+            .into_iter()
+            .map(|files| files.into_iter().map(|(f, _)| f).collect())
+            .collect()
+    }
+
+    #[test]
+    fn it_reads_lists_and_overflow_objects() {
+        let buckets: Vec<Bucket> = serde_json::from_str(
+            r#"[[["./spec/a_spec.rb", 1.5], "spec/features"], {"files": [], "overflow": true}]"#,
+        )
+        .expect("valid pre-bucketed file");
+        assert_eq!(
+            buckets,
+            vec![
+                Bucket {
+                    entries: vec![
+                        BucketEntry::File(("./spec/a_spec.rb".to_string(), Some(1.5))),
+                        BucketEntry::Pattern("spec/features".to_string()),
+                    ],
+                    overflow: false,
+                },
+                Bucket {
+                    entries: vec![],
+                    overflow: true,
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&buckets).unwrap(),
+            r#"[[["./spec/a_spec.rb",1.5],"spec/features"],{"files":[],"overflow":true}]"#
+        );
+    }
+
+    #[test]
+    fn it_resolves_files_to_their_first_matching_bucket() {
+        let buckets: Vec<Bucket> = serde_json::from_str(
+            r#"[
+                ["spec/features/**", "spec/models/a_spec.rb"],
+                [["./spec/features/b_spec.rb", 2], "spec/models"],
+                []
+            ]"#,
+        )
+        .unwrap();
+        let resolution = resolve_buckets(
+            &buckets,
+            &paths(&[
+                "./spec/features/a_spec.rb",
+                "./spec/features/b_spec.rb",
+                "./spec/models/a_spec.rb",
+                "./spec/models/b_spec.rb",
+                "./spec/other_spec.rb",
+            ]),
+        )
+        .expect("valid patterns");
+        assert_eq!(
+            resolution.pattern_matches,
+            vec![vec![1, 1], vec![1], vec![]]
+        );
+        assert_eq!(
+            bucket_files(resolution),
+            vec![
+                paths(&["./spec/features/a_spec.rb", "./spec/models/a_spec.rb"]),
+                paths(&["./spec/features/b_spec.rb", "./spec/models/b_spec.rb"]),
+                paths(&["./spec/other_spec.rb"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_deals_leftovers_round_robin_to_overflow_buckets() {
+        let buckets: Vec<Bucket> = serde_json::from_str(
+            r#"[
+                {"files": [], "overflow": true},
+                ["spec/models"],
+                {"files": [], "overflow": true}
+            ]"#,
+        )
+        .unwrap();
+        let resolution = resolve_buckets(
+            &buckets,
+            &paths(&[
+                "./spec/a_spec.rb",
+                "./spec/b_spec.rb",
+                "./spec/c_spec.rb",
+                "./spec/models/a_spec.rb",
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            bucket_files(resolution),
+            vec![
+                paths(&["./spec/a_spec.rb", "./spec/c_spec.rb"]),
+                paths(&["./spec/models/a_spec.rb"]),
+                paths(&["./spec/b_spec.rb"]),
+            ]
+        );
+    }
+}
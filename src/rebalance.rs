@@ -0,0 +1,419 @@
+use crate::output::{predicted_time, SplitFile};
+use crate::pre_bucketed::{self, Bucket, BucketEntry};
+use crate::report::imbalance;
+use crate::timings::{CostModel, FileTiming, Percent};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Pre-bucketed splits brought up to date with the spec files and their timings.
+#[derive(Debug, PartialEq)]
+pub struct Rebalance {
+    pub buckets: Vec<Bucket>,
+    pub changes: Vec<BucketChange>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BucketChange {
+    pub before_time: f64,
+    pub after_time: f64,
+    pub added: usize,
+    pub removed: usize,
+    /// Files moved in and out by the --max-imbalance pass
+    pub moved_in: usize,
+    pub moved_out: usize,
+    pub pinned: bool,
+}
+
+/// Updates hand-maintained buckets, moving as few of their files as possible so the
+/// buckets stay recognizable:
+///
+/// - files that no longer exist are dropped, globs and directories are kept
+/// - stored times are refreshed from the timings, keeping the stored time of files
+///   without timings
+/// - new spec files that no glob or directory matches are added to the least loaded
+///   bucket that isn't pinned (and is marked as overflow, if any bucket is), largest
+///   first, counting files without timings as the average file time
+/// - with `max_imbalance`, while the slowest bucket takes longer than that over the
+///   average bucket, the listed file of the slowest bucket that best evens it out
+///   with the fastest bucket is moved there, no file is moved otherwise
+pub fn rebalance(
+    buckets: Vec<Bucket>,
+    timings: &[FileTiming],
+    spec_paths: &[String],
+    pinned: &[usize],
+    max_imbalance: Option<Percent>,
+) -> Result<Rebalance, failure::Error> {
+    if let Some(index) = pinned.iter().find(|index| **index >= buckets.len()) {
+        failure::bail!(
+            "can't pin bucket {}, there are only {} buckets",
+            index,
+            buckets.len()
+        );
+    }
+
+    let times = timings
+        .iter()
+        .map(|t| (t.file_path.as_str(), t.total_time))
+        .collect::<HashMap<_, _>>();
+    let spec_paths_set = spec_paths
+        .iter()
+        .map(String::as_str)
+        .collect::<HashSet<_>>();
+    let cost_model = CostModel::default();
+
+    let resolution = pre_bucketed::resolve_buckets(&buckets, spec_paths)?;
+    // the files matched by globs and directories come after the listed files
+    let matched = resolution
+        .buckets
+        .iter()
+        .zip(&buckets)
+        .map(|(files, bucket)| {
+            files[bucket.files().count()..]
+                .iter()
+                .map(|(file_path, _)| (file_path.clone(), times.get(file_path.as_str()).cloned()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let bucket_files = |bucket: &Bucket, matched: &[SplitFile]| {
+        bucket.files().chain(matched).cloned().collect::<Vec<_>>()
+    };
+
+    let mut changes = vec![];
+    let mut rebalanced = vec![];
+    for (index, bucket) in buckets.into_iter().enumerate() {
+        let before_time = predicted_time(&bucket_files(&bucket, &matched[index]), &cost_model);
+        let listed = bucket.files().count();
+        let entries = bucket
+            .entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                BucketEntry::File((file_path, time)) => {
+                    if !spec_paths_set.contains(file_path.as_str()) {
+                        return None;
+                    }
+                    let time = times.get(file_path.as_str()).cloned().or(time);
+                    Some(BucketEntry::File((file_path, time)))
+                }
+                pattern => Some(pattern),
+            })
+            .collect::<Vec<_>>();
+        let bucket = Bucket { entries, ..bucket };
+        changes.push(BucketChange {
+            before_time,
+            after_time: 0.0,
+            added: 0,
+            removed: listed - bucket.files().count(),
+            moved_in: 0,
+            moved_out: 0,
+            pinned: pinned.contains(&index),
+        });
+        rebalanced.push(bucket);
+    }
+
+    let mut new_files = resolution
+        .leftovers
+        .iter()
+        .map(|file_path| (file_path.clone(), times.get(file_path.as_str()).cloned()))
+        .collect::<Vec<_>>();
+    let takes_new_files = |index: usize| {
+        !changes[index].pinned && (resolution.overflow.is_empty() || rebalanced[index].overflow)
+    };
+    let candidates = (0..rebalanced.len())
+        .filter(|index| takes_new_files(*index))
+        .collect::<Vec<_>>();
+    if !new_files.is_empty() && candidates.is_empty() {
+        failure::bail!(
+            "{} new spec files but every {} is pinned",
+            new_files.len(),
+            if resolution.overflow.is_empty() {
+                "bucket"
+            } else {
+                "overflow bucket"
+            }
+        );
+    }
+
+    let known_times = rebalanced
+        .iter()
+        .flat_map(Bucket::files)
+        .chain(matched.iter().flatten())
+        .chain(new_files.iter())
+        .filter_map(|(_, time)| *time)
+        .collect::<Vec<_>>();
+    let average_time = if known_times.is_empty() {
+        0.0
+    } else {
+        known_times.iter().sum::<f64>() / known_times.len() as f64
+    };
+    let placement_time = |time: &Option<f64>| time.unwrap_or(average_time);
+
+    // largest first, ties in path order so the result doesn't depend on the spec order
+    new_files.sort_by(|(a_path, a_time), (b_path, b_time)| {
+        placement_time(b_time)
+            .partial_cmp(&placement_time(a_time))
+            .unwrap()
+            .then_with(|| a_path.cmp(b_path))
+    });
+    let mut loads = rebalanced
+        .iter()
+        .zip(&matched)
+        .map(|(bucket, matched)| {
+            bucket_files(bucket, matched)
+                .iter()
+                .map(|(_, t)| placement_time(t))
+                .sum::<f64>()
+        })
+        .collect::<Vec<_>>();
+    for new_file in new_files {
+        let index = *candidates
+            .iter()
+            .min_by(|a, b| loads[**a].partial_cmp(&loads[**b]).unwrap())
+            .expect("a bucket takes new files");
+        loads[index] += placement_time(&new_file.1);
+        changes[index].added += 1;
+        rebalanced[index].entries.push(BucketEntry::File(new_file));
+    }
+
+    if let Some(Percent(max_imbalance)) = max_imbalance {
+        let unpinned = (0..rebalanced.len())
+            .filter(|index| !changes[*index].pinned)
+            .collect::<Vec<_>>();
+        while imbalance(&loads) > max_imbalance {
+            let slowest = (0..loads.len())
+                .max_by(|a, b| loads[*a].partial_cmp(&loads[*b]).unwrap())
+                .expect("there are buckets");
+            let fastest = unpinned
+                .iter()
+                .cloned()
+                .min_by(|a, b| loads[*a].partial_cmp(&loads[*b]).unwrap());
+            let fastest = match fastest {
+                Some(fastest) if !changes[slowest].pinned && fastest != slowest => fastest,
+                _ => break,
+            };
+
+            // only files shorter than the gap make the slowest bucket faster
+            let gap = loads[slowest] - loads[fastest];
+            let best_fit = rebalanced[slowest]
+                .entries
+                .iter()
+                .enumerate()
+                .filter_map(|(position, entry)| match entry {
+                    BucketEntry::File((_, time)) => Some((position, placement_time(time))),
+                    BucketEntry::Pattern(_) => None,
+                })
+                .filter(|(_, time)| *time > 0.0 && *time < gap)
+                .min_by(|(_, a), (_, b)| {
+                    (gap / 2.0 - a)
+                        .abs()
+                        .partial_cmp(&(gap / 2.0 - b).abs())
+                        .unwrap()
+                });
+            let (position, time) = match best_fit {
+                Some(best_fit) => best_fit,
+                None => break,
+            };
+
+            let entry = rebalanced[slowest].entries.remove(position);
+            rebalanced[fastest].entries.push(entry);
+            loads[slowest] -= time;
+            loads[fastest] += time;
+            changes[slowest].moved_out += 1;
+            changes[fastest].moved_in += 1;
+        }
+    }
+
+    for (index, change) in changes.iter_mut().enumerate() {
+        change.after_time = predicted_time(
+            &bucket_files(&rebalanced[index], &matched[index]),
+            &cost_model,
+        );
+    }
+    Ok(Rebalance {
+        buckets: rebalanced,
+        changes,
+    })
+}
+
+impl fmt::Display for Rebalance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, change) in self.changes.iter().enumerate() {
+            write!(
+                f,
+                "[BUCKET {} - {:.2}s -> {:.2}s] {} added, {} removed",
+                index + 1,
+                change.before_time,
+                change.after_time,
+                change.added,
+                change.removed,
+            )?;
+            if change.moved_in > 0 {
+                write!(f, ", {} moved in", change.moved_in)?;
+            }
+            if change.moved_out > 0 {
+                write!(f, ", {} moved out", change.moved_out)?;
+            }
+            writeln!(f, "{}", if change.pinned { " (pinned)" } else { "" })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timings::ft;
+
+    fn file(file_path: &str, time: Option<f64>) -> SplitFile {
+        (file_path.to_string(), time)
+    }
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn bucket(files: Vec<SplitFile>) -> Bucket {
+        Bucket {
+            entries: files.into_iter().map(BucketEntry::File).collect(),
+            overflow: false,
+        }
+    }
+
+    #[test]
+    fn it_keeps_files_in_place_and_adds_new_ones() {
+        let rebalance = rebalance(
+            vec![
+                bucket(vec![
+                    file("./spec/a_spec.rb", Some(5.0)),
+                    file("./spec/gone_spec.rb", Some(1.0)),
+                ]),
+                bucket(vec![file("./spec/b_spec.rb", Some(2.0))]),
+                bucket(vec![file("./spec/c_spec.rb", None)]),
+            ],
+            &[
+                ft("./spec/a_spec.rb", 6.0),
+                ft("./spec/d_spec.rb", 3.0),
+                ft("./spec/e_spec.rb", 1.0),
+            ],
+            &paths(&[
+                "./spec/a_spec.rb",
+                "./spec/b_spec.rb",
+                "./spec/c_spec.rb",
+                "./spec/d_spec.rb",
+                "./spec/e_spec.rb",
+                "./spec/f_spec.rb",
+            ]),
+            &[2],
+            None,
+        )
+        .expect("no errors");
+
+        assert_eq!(
+            rebalance.buckets,
+            vec![
+                bucket(vec![
+                    file("./spec/a_spec.rb", Some(6.0)),
+                    file("./spec/e_spec.rb", Some(1.0)),
+                ]),
+                bucket(vec![
+                    file("./spec/b_spec.rb", Some(2.0)),
+                    file("./spec/d_spec.rb", Some(3.0)),
+                    file("./spec/f_spec.rb", None),
+                ]),
+                bucket(vec![file("./spec/c_spec.rb", None)]),
+            ]
+        );
+        assert_eq!(
+            rebalance.to_string(),
+            "[BUCKET 1 - 6.00s -> 7.00s] 1 added, 1 removed\n\
+             [BUCKET 2 - 2.00s -> 5.00s] 2 added, 0 removed\n\
+             [BUCKET 3 - 0.00s -> 0.00s] 0 added, 0 removed (pinned)\n"
+        );
+    }
+
+    #[test]
+    fn it_keeps_patterns_and_adds_new_files_to_overflow_buckets() {
+        let buckets: Vec<Bucket> = serde_json::from_str(
+            r#"[
+                ["spec/models"],
+                [["./spec/a_spec.rb", 1]],
+                {"files": [], "overflow": true}
+            ]"#,
+        )
+        .unwrap();
+        let rebalance = rebalance(
+            buckets,
+            &[
+                ft("./spec/models/a_spec.rb", 4.0),
+                ft("./spec/b_spec.rb", 2.0),
+            ],
+            &paths(&[
+                "./spec/a_spec.rb",
+                "./spec/b_spec.rb",
+                "./spec/models/a_spec.rb",
+            ]),
+            &[],
+            None,
+        )
+        .expect("no errors");
+
+        assert_eq!(
+            serde_json::to_string(&rebalance.buckets).unwrap(),
+            r#"[["spec/models"],[["./spec/a_spec.rb",1.0]],{"files":[["./spec/b_spec.rb",2.0]],"overflow":true}]"#
+        );
+        assert_eq!(
+            rebalance.to_string(),
+            "[BUCKET 1 - 4.00s -> 4.00s] 0 added, 0 removed\n\
+             [BUCKET 2 - 1.00s -> 1.00s] 0 added, 0 removed\n\
+             [BUCKET 3 - 0.00s -> 2.00s] 1 added, 0 removed\n"
+        );
+    }
+
+    #[test]
+    fn it_moves_the_fewest_files_to_get_under_the_max_imbalance() {
+        let buckets = vec![
+            bucket(vec![
+                file("./spec/a_spec.rb", Some(6.0)),
+                file("./spec/b_spec.rb", Some(3.0)),
+                file("./spec/c_spec.rb", Some(1.0)),
+            ]),
+            bucket(vec![file("./spec/d_spec.rb", Some(2.0))]),
+        ];
+        let spec_paths = paths(&[
+            "./spec/a_spec.rb",
+            "./spec/b_spec.rb",
+            "./spec/c_spec.rb",
+            "./spec/d_spec.rb",
+        ]);
+
+        let moved = rebalance(buckets.clone(), &[], &spec_paths, &[], Some(Percent(10.0)))
+            .expect("no errors");
+        assert_eq!(
+            moved.buckets,
+            vec![
+                bucket(vec![file("./spec/a_spec.rb", Some(6.0))]),
+                bucket(vec![
+                    file("./spec/d_spec.rb", Some(2.0)),
+                    file("./spec/b_spec.rb", Some(3.0)),
+                    file("./spec/c_spec.rb", Some(1.0)),
+                ]),
+            ]
+        );
+        assert_eq!(
+            moved.to_string(),
+            "[BUCKET 1 - 10.00s -> 6.00s] 0 added, 0 removed, 2 moved out\n\
+             [BUCKET 2 - 2.00s -> 6.00s] 0 added, 0 removed, 2 moved in\n"
+        );
+
+        let pinned = rebalance(buckets.clone(), &[], &spec_paths, &[0], Some(Percent(10.0)))
+            .expect("no errors");
+        assert_eq!(pinned.buckets, buckets);
+    }
+
+    #[test]
+    fn it_rejects_invalid_pins() {
+        let buckets = vec![bucket(vec![file("./spec/a_spec.rb", Some(1.0))])];
+        let spec_paths = paths(&["./spec/a_spec.rb", "./spec/b_spec.rb"]);
+        assert!(rebalance(buckets.clone(), &[], &spec_paths, &[1], None).is_err());
+        assert!(rebalance(buckets, &[], &spec_paths, &[0], None).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+use crate::output::{format_delta, format_time, markdown_code, predicted_time};
+use crate::plan::Plan;
+use crate::timings::{percent_change, CostModel, FileTiming};
+use serde_derive::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Predicted and actual time of a node, where the actual time comes from the
+/// JUnit file the node wrote.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NodeReport {
+    /// 0-based index of the plan bucket the node ran
+    pub index: usize,
+    pub junit_file: String,
+    pub predicted_time: f64,
+    pub actual_time: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FileDeviation {
+    pub file_path: String,
+    /// None if the file wasn't covered by the timing information
+    pub predicted_time: Option<f64>,
+    pub actual_time: f64,
+    pub deviation: f64,
+}
+
+/// How well a plan predicted the times of a CI run.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RunReport {
+    pub nodes: Vec<NodeReport>,
+    /// Buckets of the plan without a JUnit file
+    pub missing_buckets: Vec<usize>,
+    pub predicted_makespan: f64,
+    pub actual_makespan: f64,
+    /// How much longer the slowest node takes than the average node, in percent
+    pub predicted_imbalance: f64,
+    pub actual_imbalance: f64,
+    /// Files whose actual time deviated most from the predicted one, largest first
+    pub deviations: Vec<FileDeviation>,
+}
+
+/// Compares the plan with the timings parsed from each node's JUnit file, keeping
+/// the `top` files that deviated most.
+///
+/// JUnit files are matched to the bucket sharing the most spec files with them,
+/// since the order they're passed in (ex. from a glob) says nothing about the node.
+/// Times only include the spec files, not the node's time spent outside of rspec, so
+/// the predicted times include the plan's file overhead but not its node overhead.
+pub fn run_report(
+    plan: &Plan,
+    junit_timings: &[(String, Vec<FileTiming>)],
+    top: usize,
+) -> Result<RunReport, failure::Error> {
+    let cost_model = CostModel {
+        node_overhead: 0.0,
+        ..plan.cost_model
+    };
+    let mut nodes = vec![];
+    let mut deviations = vec![];
+    for (junit_file, timings) in junit_timings {
+        let file_paths = timings
+            .iter()
+            .map(|t| t.file_path.as_str())
+            .collect::<HashSet<_>>();
+        let (index, overlap) = plan
+            .buckets
+            .iter()
+            .map(|files| {
+                files
+                    .iter()
+                    .filter(|(f, _)| file_paths.contains(f.as_str()))
+                    .count()
+            })
+            .enumerate()
+            .max_by_key(|&(index, overlap)| (overlap, std::cmp::Reverse(index)))
+            .unwrap_or((0, 0));
+        if overlap == 0 {
+            failure::bail!("{} doesn't share any spec file with the plan", junit_file);
+        }
+        if let Some(node) = nodes.iter().find(|n: &&NodeReport| n.index == index) {
+            failure::bail!(
+                "{} and {} both ran bucket {} of the plan",
+                node.junit_file,
+                junit_file,
+                index + 1
+            );
+        }
+
+        let predicted_times = plan.buckets[index]
+            .iter()
+            .cloned()
+            .collect::<HashMap<_, _>>();
+        for timing in timings {
+            let predicted_time = predicted_times.get(&timing.file_path).cloned().flatten();
+            deviations.push(FileDeviation {
+                file_path: timing.file_path.clone(),
+                predicted_time,
+                actual_time: timing.total_time,
+                deviation: timing.total_time - predicted_time.unwrap_or(0.0),
+            });
+        }
+
+        nodes.push(NodeReport {
+            index,
+            junit_file: junit_file.clone(),
+            predicted_time: predicted_time(&plan.buckets[index], &cost_model),
+            actual_time: timings.iter().map(|t| t.total_time).sum(),
+        });
+    }
+    nodes.sort_by_key(|n| n.index);
+    deviations.sort_by(|a, b| b.deviation.abs().partial_cmp(&a.deviation.abs()).unwrap());
+    deviations.truncate(top);
+
+    let predicted_times = plan
+        .buckets
+        .iter()
+        .map(|files| predicted_time(files, &cost_model))
+        .collect::<Vec<_>>();
+    let actual_times = nodes.iter().map(|n| n.actual_time).collect::<Vec<_>>();
+    Ok(RunReport {
+        missing_buckets: (0..plan.buckets.len())
+            .filter(|index| !nodes.iter().any(|n| n.index == *index))
+            .collect(),
+        nodes,
+        predicted_makespan: makespan(&predicted_times),
+        actual_makespan: makespan(&actual_times),
+        predicted_imbalance: imbalance(&predicted_times),
+        actual_imbalance: imbalance(&actual_times),
+        deviations,
+    })
+}
+
+fn makespan(times: &[f64]) -> f64 {
+    times.iter().cloned().fold(0.0, f64::max)
+}
+
+/// How much longer the slowest of the times is than their average, in percent.
+pub fn imbalance(times: &[f64]) -> f64 {
+    let mean = times.iter().sum::<f64>() / times.len() as f64;
+    if mean > 0.0 {
+        (makespan(times) / mean - 1.0) * 100.0
+    } else {
+        0.0
+    }
+}
+
+impl RunReport {
+    /// Renders the report as GitHub-flavored Markdown, ex. for `$GITHUB_STEP_SUMMARY`.
+    pub fn markdown(&self) -> String {
+        let mut markdown = String::new();
+        markdown.push_str("| Node | Predicted | Actual | Delta | JUnit file |\n");
+        markdown.push_str("| ---: | ---: | ---: | ---: | --- |\n");
+        for node in &self.nodes {
+            let delta = node.actual_time - node.predicted_time;
+            markdown.push_str(&format!(
+                "| {} | {:.2}s | {:.2}s | {} | {} |\n",
+                node.index + 1,
+                node.predicted_time,
+                node.actual_time,
+                format_delta(delta, percent_change(node.predicted_time, delta)),
+                markdown_code(&node.junit_file)
+            ));
+        }
+
+        let delta = self.actual_makespan - self.predicted_makespan;
+        markdown.push_str(&format!(
+            "\n**Makespan:** predicted {:.2}s, actual {:.2}s ({})  \n\
+             **Imbalance:** predicted {:.1}%, actual {:.1}% (slowest node compared to the average)\n",
+            self.predicted_makespan,
+            self.actual_makespan,
+            format_delta(delta, percent_change(self.predicted_makespan, delta)),
+            self.predicted_imbalance,
+            self.actual_imbalance
+        ));
+
+        if !self.deviations.is_empty() {
+            markdown.push_str("\n| File | Predicted | Actual | Deviation |\n");
+            markdown.push_str("| --- | ---: | ---: | ---: |\n");
+            for deviation in &self.deviations {
+                markdown.push_str(&format!(
+                    "| {} | {} | {:.2}s | {} |\n",
+                    markdown_code(&deviation.file_path),
+                    format_time(deviation.predicted_time),
+                    deviation.actual_time,
+                    format_delta(
+                        deviation.deviation,
+                        deviation
+                            .predicted_time
+                            .and_then(|t| percent_change(t, deviation.deviation))
+                    )
+                ));
+            }
+        }
+
+        if !self.missing_buckets.is_empty() {
+            markdown.push_str(&format!(
+                "\n> [!WARNING]\n> No JUnit file for buckets {}, the actual makespan may be higher.\n",
+                self.missing_buckets
+                    .iter()
+                    .map(|index| (index + 1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        markdown
+    }
+}
+
+impl fmt::Display for RunReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for node in &self.nodes {
+            let delta = node.actual_time - node.predicted_time;
+            writeln!(
+                f,
+                "[NODE {} - predicted {:.2}s, actual {:.2}s ({})] {}",
+                node.index + 1,
+                node.predicted_time,
+                node.actual_time,
+                format_delta(delta, percent_change(node.predicted_time, delta)),
+                node.junit_file
+            )?;
+        }
+
+        let delta = self.actual_makespan - self.predicted_makespan;
+        writeln!(
+            f,
+            "Makespan: predicted {:.2}s, actual {:.2}s ({})",
+            self.predicted_makespan,
+            self.actual_makespan,
+            format_delta(delta, percent_change(self.predicted_makespan, delta))
+        )?;
+        writeln!(
+            f,
+            "Imbalance: predicted {:.1}%, actual {:.1}% (slowest node compared to the average)",
+            self.predicted_imbalance, self.actual_imbalance
+        )?;
+
+        if !self.deviations.is_empty() {
+            writeln!(f, "Largest deviations:")?;
+            for deviation in &self.deviations {
+                writeln!(
+                    f,
+                    "  {}: predicted {}, actual {:.2}s ({})",
+                    deviation.file_path,
+                    format_time(deviation.predicted_time),
+                    deviation.actual_time,
+                    format_delta(
+                        deviation.deviation,
+                        deviation
+                            .predicted_time
+                            .and_then(|t| percent_change(t, deviation.deviation))
+                    )
+                )?;
+            }
+        }
+
+        if !self.missing_buckets.is_empty() {
+            writeln!(
+                f,
+                "WARNING: No JUnit file for buckets {}, the actual makespan may be higher",
+                self.missing_buckets
+                    .iter()
+                    .map(|index| (index + 1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timings::ft;
+
+    fn plan() -> Plan {
+        Plan {
+            fingerprint: "0123".to_string(),
+            total_splits: 2,
+            strategy: "greedy-largest-first".to_string(),
+            cost_model: CostModel::default(),
+            buckets: vec![
+                vec![
+                    ("./spec/a_spec.rb".to_string(), Some(6.0)),
+                    ("./spec/b_spec.rb".to_string(), None),
+                ],
+                vec![
+                    ("./spec/c_spec.rb".to_string(), Some(3.0)),
+                    ("./spec/d_spec.rb".to_string(), Some(3.0)),
+                ],
+            ],
+        }
+    }
+
+    #[test]
+    fn it_matches_junit_files_to_buckets() {
+        let report = run_report(
+            &plan(),
+            &[
+                (
+                    "node-1.xml".to_string(),
+                    vec![ft("./spec/c_spec.rb", 2.0), ft("./spec/d_spec.rb", 4.0)],
+                ),
+                (
+                    "node-0.xml".to_string(),
+                    vec![ft("./spec/a_spec.rb", 10.0), ft("./spec/b_spec.rb", 2.0)],
+                ),
+            ],
+            2,
+        )
+        .expect("no errors");
+
+        assert_eq!(
+            report
+                .nodes
+                .iter()
+                .map(|n| (
+                    n.index,
+                    n.junit_file.as_str(),
+                    n.predicted_time,
+                    n.actual_time
+                ))
+                .collect::<Vec<_>>(),
+            vec![(0, "node-0.xml", 6.0, 12.0), (1, "node-1.xml", 6.0, 6.0)]
+        );
+        assert_eq!(report.predicted_makespan, 6.0);
+        assert_eq!(report.actual_makespan, 12.0);
+        assert_eq!(report.predicted_imbalance, 0.0);
+        assert!((report.actual_imbalance - 33.3).abs() < 0.1);
+        assert_eq!(
+            report.deviations,
+            vec![
+                FileDeviation {
+                    file_path: "./spec/a_spec.rb".to_string(),
+                    predicted_time: Some(6.0),
+                    actual_time: 10.0,
+                    deviation: 4.0,
+                },
+                FileDeviation {
+                    file_path: "./spec/b_spec.rb".to_string(),
+                    predicted_time: None,
+                    actual_time: 2.0,
+                    deviation: 2.0,
+                },
+            ]
+        );
+        assert!(report.missing_buckets.is_empty());
+    }
+
+    #[test]
+    fn it_predicts_times_with_the_file_overhead_but_not_the_node_overhead() {
+        let plan = Plan {
+            cost_model: CostModel {
+                node_overhead: 2.0,
+                file_overhead: 0.5,
+            },
+            ..plan()
+        };
+        let report = run_report(
+            &plan,
+            &[("node-0.xml".to_string(), vec![ft("./spec/a_spec.rb", 10.0)])],
+            2,
+        )
+        .expect("no errors");
+
+        assert_eq!(report.nodes[0].predicted_time, 7.0);
+        assert_eq!(report.predicted_makespan, 7.0);
+        assert_eq!(report.missing_buckets, vec![1]);
+    }
+
+    #[test]
+    fn it_rejects_junit_files_of_the_same_bucket() {
+        let timings = vec![ft("./spec/a_spec.rb", 10.0)];
+        assert!(run_report(
+            &plan(),
+            &[
+                ("node-0.xml".to_string(), timings.clone()),
+                ("retry-0.xml".to_string(), timings),
+            ],
+            10,
+        )
+        .is_err());
+        assert!(run_report(
+            &plan(),
+            &[("other.xml".to_string(), vec![ft("./spec/e_spec.rb", 1.0)])],
+            10,
+        )
+        .is_err());
+    }
+}
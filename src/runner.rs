@@ -0,0 +1,163 @@
+use crate::output::SplitFile;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Builds the command to run the spec files with, forcing the JUnit formatter to
+/// write to `junit_output` so fresh timings can be parsed after the run.
+///
+/// The progress formatter is added back as passing any --format replaces the
+/// default formatter.
+pub fn rspec_command(command: &[String], junit_output: &Path, files: &[SplitFile]) -> Command {
+    let mut cmd = Command::new(&command[0]);
+    cmd.args(&command[1..])
+        .args([
+            "--format",
+            "progress",
+            "--format",
+            "RspecJunitFormatter",
+            "--out",
+        ])
+        .arg(junit_output)
+        .args(files.iter().map(|(f, _)| f));
+    cmd
+}
+
+/// Removes the JUnit output of an earlier run, so that timings are never parsed from
+/// it when the command fails before writing its own.
+pub fn remove_stale_output(junit_output: &Path) -> io::Result<()> {
+    match fs::remove_file(junit_output) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Exit code to propagate from a finished command, processes killed by a
+/// signal don't have one.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
+
+/// Value of TEST_ENV_NUMBER for the local job, following parallel_tests where the
+/// first process gets an empty value (ie. uses the default test database) unless
+/// `first_is_1` is set.
+pub fn test_env_number(index: usize, first_is_1: bool) -> String {
+    if index == 0 && !first_is_1 {
+        String::new()
+    } else {
+        (index + 1).to_string()
+    }
+}
+
+/// Command running with every line of its output prefixed, see `spawn_prefixed`.
+pub struct PrefixedChild {
+    child: Child,
+    started: Instant,
+    stdout: JoinHandle<()>,
+    stderr: JoinHandle<()>,
+}
+
+/// Spawns the command with every line of its output prefixed, so the output of
+/// concurrent commands can be told apart.
+pub fn spawn_prefixed(mut cmd: Command, prefix: String) -> io::Result<PrefixedChild> {
+    let started = Instant::now();
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    let stdout = prefix_lines(child.stdout.take().unwrap(), prefix.clone(), false);
+    let stderr = prefix_lines(child.stderr.take().unwrap(), prefix, true);
+    Ok(PrefixedChild {
+        child,
+        started,
+        stdout,
+        stderr,
+    })
+}
+
+impl PrefixedChild {
+    /// Waits for the command in the background. Joining the handle returns the exit
+    /// status and how long the command took.
+    pub fn wait_in_background(self) -> JoinHandle<io::Result<(ExitStatus, Duration)>> {
+        let PrefixedChild {
+            mut child,
+            started,
+            stdout,
+            stderr,
+        } = self;
+        thread::spawn(move || {
+            let status = child.wait()?;
+            let elapsed = started.elapsed();
+            let _ = stdout.join();
+            let _ = stderr.join();
+            Ok((status, elapsed))
+        })
+    }
+
+    /// Kills the command, ex. when another command couldn't be started.
+    pub fn kill(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = self.stdout.join();
+        let _ = self.stderr.join();
+    }
+}
+
+fn prefix_lines(
+    reader: impl Read + Send + 'static,
+    prefix: String,
+    to_stderr: bool,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut line = vec![];
+        while let Ok(read) = reader.read_until(b'\n', &mut line) {
+            if read == 0 {
+                break;
+            }
+
+            let text = String::from_utf8_lossy(&line);
+            let text = text.trim_end_matches(&['\r', '\n'][..]);
+            if to_stderr {
+                eprintln!("{}{}", prefix, text);
+            } else {
+                println!("{}{}", prefix, text);
+            }
+            line.clear();
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_appends_the_formatter_and_files() {
+        let cmd = rspec_command(
+            &[
+                "bundle".to_string(),
+                "exec".to_string(),
+                "rspec".to_string(),
+            ],
+            Path::new("rspec-results-0.xml"),
+            &[
+                ("./spec/a_spec.rb".to_string(), Some(1.0)),
+                ("./spec/b_spec.rb".to_string(), None),
+            ],
+        );
+
+        assert_eq!(
+            format!("{:?}", cmd),
+            r#""bundle" "exec" "rspec" "--format" "progress" "--format" "RspecJunitFormatter" "--out" "rspec-results-0.xml" "./spec/a_spec.rb" "./spec/b_spec.rb""#
+        );
+    }
+
+    #[test]
+    fn test_env_numbers_follow_parallel_tests() {
+        assert_eq!(test_env_number(0, false), "");
+        assert_eq!(test_env_number(1, false), "2");
+        assert_eq!(test_env_number(0, true), "1");
+    }
+}
@@ -0,0 +1,313 @@
+use crate::output::{format_delta, format_time, markdown_code};
+use crate::timings::FileTiming;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Change in time of a spec file or directory between two timing files, where a
+/// missing time means it's not in that timing file.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TimingChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub old_time: Option<f64>,
+    pub new_time: Option<f64>,
+    pub delta: f64,
+    /// None when there's no old time to compare against
+    pub percent: Option<f64>,
+}
+
+impl TimingChange {
+    fn new(path: String, old_time: Option<f64>, new_time: Option<f64>) -> TimingChange {
+        let delta = new_time.unwrap_or(0.0) - old_time.unwrap_or(0.0);
+        TimingChange {
+            path,
+            kind: match (old_time, new_time) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                _ => ChangeKind::Changed,
+            },
+            old_time,
+            new_time,
+            delta,
+            percent: percent_change(old_time.unwrap_or(0.0), delta),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TimingDiff {
+    pub old_total_time: f64,
+    pub new_total_time: f64,
+    pub delta: f64,
+    pub percent: Option<f64>,
+    /// Added, removed and changed spec files, sorted by impact
+    pub files: Vec<TimingChange>,
+    /// Spec files rolled up into their directory, sorted by impact
+    pub directories: Vec<TimingChange>,
+}
+
+/// Compares two timing files, leaving out the spec files whose time didn't change.
+pub fn diff_timings(old_timings: &[FileTiming], new_timings: &[FileTiming]) -> TimingDiff {
+    let mut times_by_path: BTreeMap<&str, (Option<f64>, Option<f64>)> = BTreeMap::new();
+    for timing in old_timings {
+        let times = times_by_path.entry(&timing.file_path).or_default();
+        times.0 = Some(times.0.unwrap_or(0.0) + timing.total_time);
+    }
+    for timing in new_timings {
+        let times = times_by_path.entry(&timing.file_path).or_default();
+        times.1 = Some(times.1.unwrap_or(0.0) + timing.total_time);
+    }
+
+    let mut times_by_directory: BTreeMap<String, (Option<f64>, Option<f64>)> = BTreeMap::new();
+    for (path, (old_time, new_time)) in &times_by_path {
+        let directory = Path::new(path)
+            .parent()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let times = times_by_directory.entry(directory).or_default();
+        if let Some(old_time) = old_time {
+            times.0 = Some(times.0.unwrap_or(0.0) + old_time);
+        }
+        if let Some(new_time) = new_time {
+            times.1 = Some(times.1.unwrap_or(0.0) + new_time);
+        }
+    }
+
+    let old_total_time = old_timings.iter().map(|t| t.total_time).sum::<f64>();
+    let new_total_time = new_timings.iter().map(|t| t.total_time).sum::<f64>();
+    let delta = new_total_time - old_total_time;
+    TimingDiff {
+        old_total_time,
+        new_total_time,
+        delta,
+        percent: percent_change(old_total_time, delta),
+        files: sorted_by_impact(
+            times_by_path
+                .into_iter()
+                .map(|(path, (old_time, new_time))| {
+                    TimingChange::new(path.to_string(), old_time, new_time)
+                })
+                .collect(),
+        ),
+        directories: sorted_by_impact(
+            times_by_directory
+                .into_iter()
+                .map(|(path, (old_time, new_time))| TimingChange::new(path, old_time, new_time))
+                .collect(),
+        ),
+    }
+}
+
+/// Leaves out unchanged entries and sorts by largest absolute delta first,
+/// keeping the path order on ties.
+fn sorted_by_impact(mut changes: Vec<TimingChange>) -> Vec<TimingChange> {
+    changes.retain(|c| c.kind != ChangeKind::Changed || c.delta != 0.0);
+    changes.sort_by(|a, b| b.delta.abs().partial_cmp(&a.delta.abs()).unwrap());
+    changes
+}
+
+/// Change relative to the old time, None when there is no old time.
+pub fn percent_change(old_time: f64, delta: f64) -> Option<f64> {
+    if old_time > 0.0 {
+        Some(delta / old_time * 100.0)
+    } else {
+        None
+    }
+}
+
+impl TimingDiff {
+    /// Renders the changes as GitHub-flavored Markdown, ex. for pull request comments.
+    pub fn markdown(&self) -> String {
+        let mut markdown = format!(
+            "**Total:** {:.2}s → {:.2}s ({})\n",
+            self.old_total_time,
+            self.new_total_time,
+            format_delta(self.delta, self.percent)
+        );
+        for (heading, changes) in &[("File", &self.files), ("Directory", &self.directories)] {
+            if changes.is_empty() {
+                continue;
+            }
+            markdown.push_str(&format!(
+                "\n| {} | Change | Old | New | Delta |\n| --- | --- | ---: | ---: | ---: |\n",
+                heading
+            ));
+            for change in changes.iter() {
+                markdown.push_str(&format!(
+                    "| {} | {} | {} | {} | {} |\n",
+                    markdown_code(&change.path),
+                    format!("{:?}", change.kind).to_lowercase(),
+                    format_time(change.old_time),
+                    format_time(change.new_time),
+                    format_delta(change.delta, change.percent)
+                ));
+            }
+        }
+        markdown
+    }
+}
+
+impl fmt::Display for TimingDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Total: {:.2}s -> {:.2}s ({})",
+            self.old_total_time,
+            self.new_total_time,
+            format_delta(self.delta, self.percent)
+        )?;
+        for (heading, changes) in &[("Files", &self.files), ("Directories", &self.directories)] {
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(f, "\n{}:", heading)?;
+            for change in changes.iter() {
+                writeln!(
+                    f,
+                    "  {:<8} {} {} -> {} ({})",
+                    format!("{:?}", change.kind).to_lowercase(),
+                    change.path,
+                    format_time(change.old_time),
+                    format_time(change.new_time),
+                    format_delta(change.delta, change.percent)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A percentage threshold, ex. '20%' or '20'.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percent(pub f64);
+
+impl FromStr for Percent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim()
+            .trim_end_matches('%')
+            .parse::<f64>()
+            .map(Percent)
+            .map_err(|_| format!("expected a percentage like '20%', got '{}'", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timings::ft;
+
+    #[test]
+    fn it_diffs_files_and_directories() {
+        let diff = diff_timings(
+            &[
+                ft("./spec/models/a_spec.rb", 10.0),
+                ft("./spec/models/b_spec.rb", 4.0),
+                ft("./spec/lib/c_spec.rb", 6.0),
+            ],
+            &[
+                ft("./spec/models/a_spec.rb", 15.0),
+                ft("./spec/models/b_spec.rb", 4.0),
+                ft("./spec/lib/d_spec.rb", 2.0),
+            ],
+        );
+
+        assert_eq!(diff.old_total_time, 20.0);
+        assert_eq!(diff.new_total_time, 21.0);
+        assert_eq!(diff.percent, Some(5.0));
+        assert_eq!(
+            diff.files,
+            vec![
+                TimingChange {
+                    path: "./spec/lib/c_spec.rb".to_string(),
+                    kind: ChangeKind::Removed,
+                    old_time: Some(6.0),
+                    new_time: None,
+                    delta: -6.0,
+                    percent: Some(-100.0),
+                },
+                TimingChange {
+                    path: "./spec/models/a_spec.rb".to_string(),
+                    kind: ChangeKind::Changed,
+                    old_time: Some(10.0),
+                    new_time: Some(15.0),
+                    delta: 5.0,
+                    percent: Some(50.0),
+                },
+                TimingChange {
+                    path: "./spec/lib/d_spec.rb".to_string(),
+                    kind: ChangeKind::Added,
+                    old_time: None,
+                    new_time: Some(2.0),
+                    delta: 2.0,
+                    percent: None,
+                },
+            ]
+        );
+        assert_eq!(
+            diff.directories
+                .iter()
+                .map(|d| (d.path.as_str(), d.delta))
+                .collect::<Vec<_>>(),
+            vec![("./spec/models", 5.0), ("./spec/lib", -4.0)]
+        );
+    }
+
+    #[test]
+    fn it_prints_changes() {
+        let diff = diff_timings(
+            &[ft("./spec/a_spec.rb", 10.0)],
+            &[ft("./spec/a_spec.rb", 12.0), ft("./spec/b_spec.rb", 1.0)],
+        );
+        assert_eq!(
+            diff.to_string(),
+            "Total: 10.00s -> 13.00s (+3.00s, +30.0%)\n\
+             \n\
+             Files:\n  \
+             changed  ./spec/a_spec.rb 10.00s -> 12.00s (+2.00s, +20.0%)\n  \
+             added    ./spec/b_spec.rb - -> 1.00s (+1.00s)\n\
+             \n\
+             Directories:\n  \
+             changed  ./spec 10.00s -> 13.00s (+3.00s, +30.0%)\n"
+        );
+    }
+
+    #[test]
+    fn it_renders_markdown() {
+        let diff = diff_timings(
+            &[ft("./spec/a_spec.rb", 10.0)],
+            &[ft("./spec/a_spec.rb", 12.0)],
+        );
+        assert_eq!(
+            diff.markdown(),
+            "**Total:** 10.00s → 12.00s (+2.00s, +20.0%)\n\
+             \n\
+             | File | Change | Old | New | Delta |\n\
+             | --- | --- | ---: | ---: | ---: |\n\
+             | `./spec/a_spec.rb` | changed | 10.00s | 12.00s | +2.00s, +20.0% |\n\
+             \n\
+             | Directory | Change | Old | New | Delta |\n\
+             | --- | --- | ---: | ---: | ---: |\n\
+             | `./spec` | changed | 10.00s | 12.00s | +2.00s, +20.0% |\n"
+        );
+    }
+
+    #[test]
+    fn it_parses_percentages() {
+        assert_eq!("20%".parse::<Percent>(), Ok(Percent(20.0)));
+        assert_eq!("7.5".parse::<Percent>(), Ok(Percent(7.5)));
+        assert!("fast".parse::<Percent>().is_err());
+    }
+}
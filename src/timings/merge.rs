@@ -0,0 +1,46 @@
+use crate::timings::FileTiming;
+use std::collections::HashMap;
+
+/// Updates the timings with newer timings, keeping the timings of files that
+/// weren't part of the newer run. Files only in the newer timings are appended.
+pub fn merge_timings(timings: &[FileTiming], newer_timings: &[FileTiming]) -> Vec<FileTiming> {
+    let mut newer_timings_by_path = newer_timings
+        .iter()
+        .map(|t| (t.file_path.as_str(), t))
+        .collect::<HashMap<_, _>>();
+
+    let mut merged_timings = timings
+        .iter()
+        .map(|t| {
+            newer_timings_by_path
+                .remove(t.file_path.as_str())
+                .unwrap_or(t)
+                .clone()
+        })
+        .collect::<Vec<_>>();
+
+    merged_timings.extend(
+        newer_timings
+            .iter()
+            .filter(|t| newer_timings_by_path.contains_key(t.file_path.as_str()))
+            .cloned(),
+    );
+    merged_timings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timings::ft;
+
+    #[test]
+    fn it_replaces_and_appends_newer_timings() {
+        assert_eq!(
+            merge_timings(
+                &[ft("a", 10.0), ft("b", 20.0), ft("c", 15.0)],
+                &[ft("d", 5.0), ft("b", 22.0)]
+            ),
+            vec![ft("a", 10.0), ft("b", 22.0), ft("c", 15.0), ft("d", 5.0)]
+        );
+    }
+}
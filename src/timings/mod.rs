@@ -1,13 +1,53 @@
 use serde_derive::{Deserialize, Serialize};
+use std::path::Path;
 
+mod diff;
+mod merge;
 mod parse;
+mod rollup;
 mod split;
 
+pub use self::diff::*;
+pub use self::merge::*;
 pub use self::parse::*;
+pub use self::rollup::*;
 pub use self::split::*;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
 pub struct FileTiming {
     pub file_path: String,
     pub total_time: f64,
+    /// Part of total_time that isn't from any example (ex. before(:all) hooks),
+    /// attributed to the file from the suite-level timings
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub unattributed_time: f64,
+    /// Examples of the file in the order they ran, only kept when parsing with
+    /// examples (see 'parse --with-examples')
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<ExampleTiming>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ExampleTiming {
+    pub name: String,
+    pub time: f64,
+}
+
+/// Name of a spec file without its directory or extension (ex. user_spec)
+pub fn get_file_stem(path: &Path) -> String {
+    path.file_stem().unwrap().to_str().unwrap().to_string()
+}
+
+/// Timing of a file without unattributed time or examples, shared by the tests.
+#[cfg(test)]
+pub fn ft(file_path: impl Into<String>, total_time: f64) -> FileTiming {
+    FileTiming {
+        file_path: file_path.into(),
+        total_time,
+        ..Default::default()
+    }
+}
+
+fn is_zero(time: &f64) -> bool {
+    *time == 0.0
 }
@@ -1,6 +1,6 @@
-use crate::timings::FileTiming;
+use crate::timings::{ExampleTiming, FileTiming};
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 lazy_static::lazy_static! {
     static ref KV_RE: Regex = Regex::new(r##"\s*(\w+)="([^"]+)"\s*"##).unwrap();
@@ -8,10 +8,105 @@ lazy_static::lazy_static! {
 
 pub fn parse_rspec_output(
     rspec_output: impl AsRef<str>,
+) -> Result<Vec<FileTiming>, failure::Error> {
+    let mut file_timings = parse_rspec_output_with_examples(rspec_output)?;
+    for file_timing in &mut file_timings {
+        file_timing.examples.clear();
+    }
+    Ok(file_timings)
+}
+
+/// Parses the rspec output like `parse_rspec_output`, keeping the time of every
+/// example in the order they ran.
+pub fn parse_rspec_output_with_examples(
+    rspec_output: impl AsRef<str>,
 ) -> Result<Vec<FileTiming>, failure::Error> {
     let mut file_paths_to_total_times = HashMap::new();
-    for line in rspec_output.as_ref().lines() {
+    let mut file_paths_to_unattributed_times = HashMap::new();
+    let mut file_paths_to_examples: HashMap<String, Vec<ExampleTiming>> = HashMap::new();
+    for suite in parse_suites(rspec_output.as_ref())? {
+        for (file_path, example) in &suite.examples {
+            *file_paths_to_total_times
+                .entry(file_path.clone())
+                .or_insert(0.0) += example.time;
+            file_paths_to_examples
+                .entry(file_path.clone())
+                .or_default()
+                .push(example.clone());
+        }
+
+        for (file_path, time) in suite.unattributed_times() {
+            *file_paths_to_total_times
+                .entry(file_path.clone())
+                .or_insert(0.0) += time;
+            *file_paths_to_unattributed_times
+                .entry(file_path)
+                .or_insert(0.0) += time;
+        }
+    }
+
+    Ok(file_paths_to_total_times
+        .into_iter()
+        .map(|(file_path, total_time)| FileTiming {
+            unattributed_time: file_paths_to_unattributed_times
+                .get(&file_path)
+                .cloned()
+                .unwrap_or(0.0),
+            examples: file_paths_to_examples
+                .remove(&file_path)
+                .unwrap_or_default(),
+            file_path,
+            total_time,
+        })
+        .collect())
+}
+
+/// Examples of a <testsuite>, examples outside of any <testsuite> are grouped into
+/// a suite without a time.
+struct Suite {
+    time: Option<f64>,
+    /// File path and timing of every example in the suite
+    examples: Vec<(String, ExampleTiming)>,
+}
+
+impl Suite {
+    /// Splits the suite time that isn't part of any example between the suite's files.
+    ///
+    /// Formatters that write a <testsuite> per file let the time be attributed to the
+    /// file it was spent in, otherwise it's spread proportionally to the example times.
+    fn unattributed_times(&self) -> Vec<(String, f64)> {
+        let mut file_times: HashMap<&str, f64> = HashMap::new();
+        for (file_path, example) in &self.examples {
+            *file_times.entry(file_path).or_insert(0.0) += example.time;
+        }
+
+        let example_time: f64 = file_times.values().sum();
+        let unattributed_time = match self.time {
+            Some(time) if time > example_time && !file_times.is_empty() => time - example_time,
+            _ => return vec![],
+        };
+
+        let file_count = file_times.len() as f64;
+        file_times
+            .into_iter()
+            .map(|(file_path, file_time)| {
+                let share = if example_time > 0.0 {
+                    file_time / example_time
+                } else {
+                    1.0 / file_count
+                };
+                (file_path.to_string(), unattributed_time * share)
+            })
+            .collect()
+    }
+}
+
+fn parse_suites(rspec_output: &str) -> Result<Vec<Suite>, failure::Error> {
+    let mut suites = vec![];
+    for line in rspec_output.lines() {
+        let is_suite = line.trim_start().starts_with("<testsuite ");
         let mut file_path = None;
+        let mut name = None;
         let mut time = None;
         for caps in KV_RE.captures_iter(line) {
             let key = &caps[1];
@@ -19,23 +114,93 @@ pub fn parse_rspec_output(
 
             match key {
                 "file" => file_path = Some(value.to_string()),
+                "name" => name = Some(unescape_xml(value)),
                 "time" => time = Some(value.parse::<f64>()?),
                 _ => (),
             }
         }
 
-        if let (Some(file_path), Some(time)) = (file_path, time) {
-            *file_paths_to_total_times.entry(file_path).or_insert(0.0) += time
+        if is_suite {
+            suites.push(Suite {
+                time,
+                examples: vec![],
+            });
+        } else if let (Some(file_path), Some(time)) = (file_path, time) {
+            if suites.is_empty() {
+                suites.push(Suite {
+                    time: None,
+                    examples: vec![],
+                });
+            }
+            suites.last_mut().unwrap().examples.push((
+                file_path,
+                ExampleTiming {
+                    name: name.unwrap_or_default(),
+                    time,
+                },
+            ));
         }
     }
+    Ok(suites)
+}
 
-    Ok(file_paths_to_total_times
-        .into_iter()
-        .map(|(file_path, total_time)| FileTiming {
-            file_path,
-            total_time,
-        })
-        .collect())
+/// Totals of rspec timing information, used to estimate the time spent outside of examples.
+#[derive(Debug, PartialEq)]
+pub struct SuiteTotals {
+    /// Sum of the <testsuite time="..."> values
+    pub suite_time: f64,
+    /// Sum of the <testcase time="..."> values
+    pub example_time: f64,
+    /// Part of the suite time outside of the examples that 'parse' already adds to the
+    /// time of the files (see `FileTiming::unattributed_time`)
+    pub attributed_time: f64,
+    pub file_count: usize,
+}
+
+impl SuiteTotals {
+    /// Time of the suite that isn't attributed to any example or file, ex. loading
+    /// spec files outside of any <testsuite> of a file.
+    pub fn unattributed_time(&self) -> f64 {
+        (self.suite_time - self.example_time - self.attributed_time).max(0.0)
+    }
+
+    /// Estimated overhead of each spec file, spreading the unattributed time evenly.
+    pub fn file_overhead(&self) -> f64 {
+        if self.file_count == 0 {
+            return 0.0;
+        }
+
+        self.unattributed_time() / self.file_count as f64
+    }
+}
+
+pub fn parse_suite_totals(rspec_output: impl AsRef<str>) -> Result<SuiteTotals, failure::Error> {
+    let suites = parse_suites(rspec_output.as_ref())?;
+    let examples = suites.iter().flat_map(|s| s.examples.iter());
+    Ok(SuiteTotals {
+        suite_time: suites.iter().filter_map(|s| s.time).sum(),
+        example_time: examples.clone().map(|(_, e)| e.time).sum(),
+        attributed_time: suites
+            .iter()
+            .flat_map(|s| s.unattributed_times())
+            .map(|(_, time)| time)
+            .sum(),
+        file_count: examples
+            .map(|(f, _)| f.as_str())
+            .collect::<HashSet<_>>()
+            .len(),
+    })
+}
+
+/// Unescapes the predefined XML entities of an attribute value.
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
 }
 
 #[cfg(test)]
@@ -55,8 +220,126 @@ mod tests {
             file_timings,
             vec![FileTiming {
                 file_path: "./spec/lib/deliveries/worker_spec.rb".to_string(),
-                total_time: 0.584580
+                total_time: 0.584580,
+                unattributed_time: 0.0,
+                examples: vec![],
             }]
         )
     }
+
+    #[test]
+    fn it_keeps_examples_in_the_order_they_ran() {
+        let file_timings = parse_rspec_output_with_examples(r####"
+            <testsuite name="rspec" tests="2" time="3.0">
+            <testcase classname="spec.models.app_spec" name="App &quot;saves&quot; &amp; loads" file="./spec/models/app_spec.rb" time="2.5"></testcase>
+            <testcase classname="spec.models.app_spec" name="App validates" file="./spec/models/app_spec.rb" time="0.5"></testcase>
+            </testsuite>
+        "####).expect("no errors");
+
+        assert_eq!(
+            file_timings[0].examples,
+            vec![
+                ExampleTiming {
+                    name: r#"App "saves" & loads"#.to_string(),
+                    time: 2.5,
+                },
+                ExampleTiming {
+                    name: "App validates".to_string(),
+                    time: 0.5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_totals_unattributed_suite_time() {
+        let suite_totals = parse_suite_totals(r####"
+            <testsuite name="rspec" tests="3" skipped="0" failures="0" errors="0" time="12.5" timestamp="2019-01-08T16:38:12-08:00" hostname="ci">
+            <testcase classname="spec.models.app_spec" name="Some spec name" file="./spec/models/app_spec.rb" time="2.5"></testcase>
+            <testcase classname="spec.models.app_spec" name="Some spec name 2" file="./spec/models/app_spec.rb" time="1.5"></testcase>
+            <testcase classname="spec.models.player_spec" name="Some spec name" file="./spec/models/player_spec.rb" time="4.5"></testcase>
+            </testsuite>
+            <testsuite name="rspec" tests="0" skipped="0" failures="0" errors="1" time="2.0" timestamp="2019-01-08T16:38:12-08:00" hostname="ci">
+            </testsuite>
+        "####).expect("no errors");
+
+        assert_eq!(
+            suite_totals,
+            SuiteTotals {
+                suite_time: 14.5,
+                example_time: 8.5,
+                attributed_time: 4.0,
+                file_count: 2,
+            }
+        );
+        assert_eq!(suite_totals.unattributed_time(), 2.0);
+        assert_eq!(suite_totals.file_overhead(), 1.0);
+    }
+
+    fn sorted(mut file_timings: Vec<FileTiming>) -> Vec<FileTiming> {
+        file_timings.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        file_timings
+    }
+
+    #[test]
+    fn it_spreads_unattributed_suite_time_proportionally() {
+        let file_timings = parse_rspec_output(r####"
+            <testsuite name="rspec" tests="3" skipped="0" failures="0" errors="0" time="12.5" timestamp="2019-01-08T16:38:12-08:00" hostname="ci">
+            <testcase classname="spec.models.app_spec" name="Some spec name" file="./spec/models/app_spec.rb" time="2.5"></testcase>
+            <testcase classname="spec.models.app_spec" name="Some spec name 2" file="./spec/models/app_spec.rb" time="1.5"></testcase>
+            <testcase classname="spec.models.player_spec" name="Some spec name" file="./spec/models/player_spec.rb" time="4.0"></testcase>
+            </testsuite>
+        "####).expect("no errors");
+
+        assert_eq!(
+            sorted(file_timings),
+            vec![
+                FileTiming {
+                    file_path: "./spec/models/app_spec.rb".to_string(),
+                    total_time: 6.25,
+                    unattributed_time: 2.25,
+                    examples: vec![],
+                },
+                FileTiming {
+                    file_path: "./spec/models/player_spec.rb".to_string(),
+                    total_time: 6.25,
+                    unattributed_time: 2.25,
+                    examples: vec![],
+                }
+            ]
+        )
+    }
+
+    #[test]
+    fn it_attributes_suite_time_to_per_file_suites() {
+        let file_timings = parse_rspec_output(r####"
+            <testsuites>
+            <testsuite name="spec.models.app_spec" file="./spec/models/app_spec.rb" tests="2" time="10.0">
+            <testcase classname="spec.models.app_spec" name="Some spec name" file="./spec/models/app_spec.rb" time="2.5"></testcase>
+            <testcase classname="spec.models.app_spec" name="Some spec name 2" file="./spec/models/app_spec.rb" time="1.5"></testcase>
+            </testsuite>
+            <testsuite name="spec.models.player_spec" file="./spec/models/player_spec.rb" tests="1" time="4.0">
+            <testcase classname="spec.models.player_spec" name="Some spec name" file="./spec/models/player_spec.rb" time="4.0"></testcase>
+            </testsuite>
+            </testsuites>
+        "####).expect("no errors");
+
+        assert_eq!(
+            sorted(file_timings),
+            vec![
+                FileTiming {
+                    file_path: "./spec/models/app_spec.rb".to_string(),
+                    total_time: 10.0,
+                    unattributed_time: 6.0,
+                    examples: vec![],
+                },
+                FileTiming {
+                    file_path: "./spec/models/player_spec.rb".to_string(),
+                    total_time: 4.0,
+                    unattributed_time: 0.0,
+                    examples: vec![],
+                }
+            ]
+        )
+    }
 }
@@ -0,0 +1,219 @@
+use crate::output::markdown_code;
+use crate::timings::FileTiming;
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How spec files are grouped when rolling up their time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rollup {
+    Dir,
+    Codeowners,
+}
+
+impl Rollup {
+    pub fn variants() -> &'static [&'static str] {
+        &["dir", "codeowners"]
+    }
+}
+
+impl FromStr for Rollup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dir" => Ok(Rollup::Dir),
+            "codeowners" => Ok(Rollup::Codeowners),
+            _ => Err(format!(
+                "unknown rollup '{}', expected one of: {}",
+                s,
+                Rollup::variants().join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RollupGroup {
+    pub name: String,
+    pub time: f64,
+    pub file_count: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RollupReport {
+    pub total_time: f64,
+    /// Groups sorted by time, largest first
+    pub groups: Vec<RollupGroup>,
+    /// Spec files that don't belong to any group
+    pub ungrouped: Vec<FileTiming>,
+}
+
+/// Sums the time of the spec files in each group, where `group` names the group
+/// of a spec file path or returns None if it doesn't belong to one.
+pub fn rollup_timings(
+    timings: &[FileTiming],
+    group: impl Fn(&str) -> Option<String>,
+) -> RollupReport {
+    let mut groups: BTreeMap<String, RollupGroup> = BTreeMap::new();
+    let mut ungrouped = vec![];
+    for timing in timings {
+        match group(&timing.file_path) {
+            Some(name) => {
+                let group = groups.entry(name.clone()).or_insert(RollupGroup {
+                    name,
+                    time: 0.0,
+                    file_count: 0,
+                });
+                group.time += timing.total_time;
+                group.file_count += 1;
+            }
+            None => ungrouped.push(timing.clone()),
+        }
+    }
+
+    let mut groups = groups.into_values().collect::<Vec<_>>();
+    groups.sort_by(|a, b| b.time.partial_cmp(&a.time).unwrap());
+    ungrouped.sort_by(|a, b| b.total_time.partial_cmp(&a.total_time).unwrap());
+    RollupReport {
+        total_time: timings.iter().map(|t| t.total_time).sum(),
+        groups,
+        ungrouped,
+    }
+}
+
+/// Directory of the spec file, keeping at most `depth` directories
+/// (ex. './spec/models' for './spec/models/admin/user_spec.rb' at depth 2).
+pub fn directory_prefix(file_path: &str, depth: usize) -> String {
+    let directory = Path::new(file_path.trim_start_matches("./"))
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .take(depth)
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .unwrap_or_default();
+    format!("./{}", directory)
+}
+
+impl RollupReport {
+    fn share(&self, time: f64) -> f64 {
+        if self.total_time > 0.0 {
+            time / self.total_time * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl RollupReport {
+    /// Renders the groups and the files without a group as GitHub-flavored Markdown.
+    pub fn markdown(&self) -> String {
+        let mut markdown = String::new();
+        markdown.push_str("| Group | Time | Files | Share |\n");
+        markdown.push_str("| --- | ---: | ---: | ---: |\n");
+        for group in &self.groups {
+            markdown.push_str(&format!(
+                "| {} | {:.2}s | {} | {:.1}% |\n",
+                markdown_code(&group.name),
+                group.time,
+                group.file_count,
+                self.share(group.time)
+            ));
+        }
+
+        if !self.ungrouped.is_empty() {
+            markdown.push_str("\n| Unowned file | Time | Share |\n");
+            markdown.push_str("| --- | ---: | ---: |\n");
+            for timing in &self.ungrouped {
+                markdown.push_str(&format!(
+                    "| {} | {:.2}s | {:.1}% |\n",
+                    markdown_code(&timing.file_path),
+                    timing.total_time,
+                    self.share(timing.total_time)
+                ));
+            }
+        }
+        markdown
+    }
+}
+
+impl fmt::Display for RollupReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for group in &self.groups {
+            writeln!(
+                f,
+                "[{} - {:.2}s] {} files, {:.1}% of {:.2}s",
+                group.name,
+                group.time,
+                group.file_count,
+                self.share(group.time),
+                self.total_time
+            )?;
+        }
+
+        if !self.ungrouped.is_empty() {
+            let ungrouped_time = self.ungrouped.iter().map(|t| t.total_time).sum::<f64>();
+            writeln!(
+                f,
+                "Unowned: {:.2}s, {} files, {:.1}% of {:.2}s",
+                ungrouped_time,
+                self.ungrouped.len(),
+                self.share(ungrouped_time),
+                self.total_time
+            )?;
+            for timing in &self.ungrouped {
+                writeln!(f, "  {}:{:.2}s", timing.file_path, timing.total_time)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timings::ft;
+
+    #[test]
+    fn it_takes_directories_up_to_the_depth() {
+        assert_eq!(
+            directory_prefix("./spec/models/admin/user_spec.rb", 2),
+            "./spec/models"
+        );
+        assert_eq!(directory_prefix("./spec/user_spec.rb", 2), "./spec");
+        assert_eq!(directory_prefix("spec/models/user_spec.rb", 1), "./spec");
+    }
+
+    #[test]
+    fn it_rolls_up_groups_by_time() {
+        let report = rollup_timings(
+            &[
+                ft("./spec/lib/a_spec.rb", 1.0),
+                ft("./spec/models/b_spec.rb", 2.0),
+                ft("./spec/models/c_spec.rb", 3.0),
+                ft("./spec/d_spec.rb", 4.0),
+            ],
+            |path| {
+                if path.starts_with("./spec/d") {
+                    None
+                } else {
+                    Some(directory_prefix(path, 2))
+                }
+            },
+        );
+
+        assert_eq!(
+            report.to_string(),
+            "[./spec/models - 5.00s] 2 files, 50.0% of 10.00s\n\
+             [./spec/lib - 1.00s] 1 files, 10.0% of 10.00s\n\
+             Unowned: 4.00s, 1 files, 40.0% of 10.00s\n  \
+             ./spec/d_spec.rb:4.00s\n"
+        );
+    }
+}
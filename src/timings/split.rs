@@ -1,15 +1,59 @@
 use crate::timings::FileTiming;
+use serde_derive::{Deserialize, Serialize};
+
+/// Name of the algorithm used by `split_timings`, recorded in plans and fingerprints so
+/// that splits computed by different algorithms are never mixed.
+pub const SPLIT_STRATEGY: &str = "greedy-largest-first";
+
+/// Fixed costs that aren't part of the timings, ex. booting Rails before running any
+/// spec on a node, or loading a spec file before its first example.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostModel {
+    /// Seconds each node (or process) spends before running any spec file
+    pub node_overhead: f64,
+    /// Seconds each spec file costs on top of its timing
+    pub file_overhead: f64,
+}
+
+impl CostModel {
+    /// Predicted time of running the spec file.
+    pub fn file_time(&self, timing: &FileTiming) -> f64 {
+        timing.total_time + self.file_overhead
+    }
+
+    /// Name of the split strategy with this cost model, for plans and fingerprints.
+    pub fn strategy(&self) -> String {
+        if *self == CostModel::default() {
+            return SPLIT_STRATEGY.to_string();
+        }
+
+        format!(
+            "{}(node_overhead={},file_overhead={})",
+            SPLIT_STRATEGY, self.node_overhead, self.file_overhead
+        )
+    }
+}
 
 /// Splits timings into N (where N is total_splits) buckets, attempting to balance
 /// the buckets as much as possible.
 ///
 /// This is a deterministic algorithm and must always produce the same result for multiple runs.
 pub fn split_timings(timings: &[FileTiming], total_splits: u32) -> Vec<Vec<FileTiming>> {
+    split_timings_with_cost(timings, total_splits, &CostModel::default())
+}
+
+/// Splits timings like `split_timings`, balancing the buckets by their predicted time
+/// under the cost model.
+pub fn split_timings_with_cost(
+    timings: &[FileTiming],
+    total_splits: u32,
+    cost_model: &CostModel,
+) -> Vec<Vec<FileTiming>> {
     if total_splits == 0 {
         return vec![];
     }
 
-    let mut timings = timings.iter().cloned().collect::<Vec<_>>();
+    let mut timings = timings.to_vec();
     // descending order
     timings.sort_by(|a, b| b.total_time.partial_cmp(&a.total_time).unwrap());
 
@@ -35,7 +79,10 @@ pub fn split_timings(timings: &[FileTiming], total_splits: u32) -> Vec<Vec<FileT
             }
         }
 
-        buckets[min_bucket_index].total_time += timing.total_time;
+        if buckets[min_bucket_index].timings.is_empty() {
+            buckets[min_bucket_index].total_time += cost_model.node_overhead;
+        }
+        buckets[min_bucket_index].total_time += cost_model.file_time(&timing);
         buckets[min_bucket_index].timings.push(timing);
     }
 
@@ -45,17 +92,105 @@ pub fn split_timings(timings: &[FileTiming], total_splits: u32) -> Vec<Vec<FileT
         .collect()
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Splits timings into N nodes (where N is total_splits) that each run `processes_per_node`
+/// processes at once, returning the buckets of every process grouped by node.
+///
+/// A node only finishes once its slowest process does, so from largest timing to smallest
+/// each timing goes to the node whose finish time grows the least (preferring the node with
+/// the least total time on ties), into that node's least loaded process.
+///
+/// This is a deterministic algorithm and must always produce the same result for multiple runs.
+pub fn split_timings_per_node(
+    timings: &[FileTiming],
+    total_splits: u32,
+    processes_per_node: u32,
+    cost_model: &CostModel,
+) -> Result<Vec<Vec<Vec<FileTiming>>>, failure::Error> {
+    if processes_per_node == 0 {
+        failure::bail!("a node needs at least 1 process, got 0 processes per node");
+    }
+    if total_splits == 0 {
+        return Ok(vec![]);
+    }
+
+    let mut timings = timings.to_vec();
+    // descending order
+    timings.sort_by(|a, b| b.total_time.partial_cmp(&a.total_time).unwrap());
+
+    struct NodeAggregator {
+        processes: Vec<Vec<FileTiming>>,
+        process_times: Vec<f64>,
+    }
+
+    impl NodeAggregator {
+        fn min_process_index(&self) -> usize {
+            let mut min_process_index = 0;
+            for (index, time) in self.process_times.iter().enumerate().skip(1) {
+                if *time < self.process_times[min_process_index] {
+                    min_process_index = index;
+                }
+            }
+            min_process_index
+        }
+
+        fn total_time(&self) -> f64 {
+            self.process_times.iter().sum()
+        }
+
+        /// Time the node finishes at if the timing is added to its least loaded process.
+        fn finish_time_with(&self, timing: &FileTiming, cost_model: &CostModel) -> f64 {
+            let min_process_index = self.min_process_index();
+            let mut added_time =
+                self.process_times[min_process_index] + cost_model.file_time(timing);
+            if self.processes[min_process_index].is_empty() {
+                added_time += cost_model.node_overhead;
+            }
+            self.process_times
+                .iter()
+                .cloned()
+                .fold(added_time, f64::max)
+        }
+    }
+
+    let mut nodes = vec![];
+    for _ in 0..total_splits {
+        nodes.push(NodeAggregator {
+            processes: vec![vec![]; processes_per_node as usize],
+            process_times: vec![0.0; processes_per_node as usize],
+        });
+    }
+
+    // from largest timing to smallest
+    for timing in timings {
+        let mut best_node_index = 0;
+        for (index, node) in nodes.iter().enumerate().skip(1) {
+            let best_node = &nodes[best_node_index];
+            let finish_time = node.finish_time_with(&timing, cost_model);
+            let best_finish_time = best_node.finish_time_with(&timing, cost_model);
+            if finish_time < best_finish_time
+                || (finish_time == best_finish_time && node.total_time() < best_node.total_time())
+            {
+                best_node_index = index;
+            }
+        }
 
-    fn ft(file_path: impl Into<String>, total_time: f64) -> FileTiming {
-        FileTiming {
-            file_path: file_path.into(),
-            total_time,
+        let node = &mut nodes[best_node_index];
+        let process_index = node.min_process_index();
+        if node.processes[process_index].is_empty() {
+            node.process_times[process_index] += cost_model.node_overhead;
         }
+        node.process_times[process_index] += cost_model.file_time(&timing);
+        node.processes[process_index].push(timing);
     }
 
+    Ok(nodes.into_iter().map(|node| node.processes).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timings::ft;
+
     fn check_produces_the_same_result_over_multiple_runs_and_ret(
         timings: Vec<FileTiming>,
         total_splits: u32,
@@ -93,4 +228,85 @@ mod tests {
             ]
         )
     }
+
+    #[test]
+    fn per_node_with_one_process_matches_split_timings() {
+        let timings = vec![ft("a", 10.0), ft("b", 20.0), ft("c", 15.0), ft("d", 5.0)];
+        assert_eq!(
+            split_timings_per_node(&timings, 2, 1, &CostModel::default()).unwrap(),
+            split_timings(&timings, 2)
+                .into_iter()
+                .map(|bucket| vec![bucket])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn per_node_rejects_nodes_without_processes() {
+        assert!(split_timings_per_node(&[ft("a", 1.0)], 2, 0, &CostModel::default()).is_err());
+    }
+
+    #[test]
+    fn per_node_balances_the_slowest_process_of_each_node() {
+        let buckets = split_timings_per_node(
+            &[
+                ft("a", 12.0),
+                ft("b", 10.0),
+                ft("c", 3.0),
+                ft("d", 3.0),
+                ft("e", 3.0),
+                ft("f", 3.0),
+            ],
+            2,
+            2,
+            &CostModel::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            buckets,
+            vec![
+                vec![vec![ft("a", 12.0)], vec![ft("f", 3.0)]],
+                vec![
+                    vec![ft("b", 10.0)],
+                    vec![ft("c", 3.0), ft("d", 3.0), ft("e", 3.0)]
+                ]
+            ]
+        );
+    }
+
+    #[test]
+    fn file_overhead_balances_by_number_of_files() {
+        let timings = vec![
+            ft("a", 10.0),
+            ft("b", 2.0),
+            ft("c", 2.0),
+            ft("d", 2.0),
+            ft("e", 2.0),
+        ];
+        assert_eq!(
+            split_timings(&timings, 2),
+            vec![
+                vec![ft("a", 10.0)],
+                vec![ft("b", 2.0), ft("c", 2.0), ft("d", 2.0), ft("e", 2.0)]
+            ]
+        );
+
+        let cost_model = CostModel {
+            node_overhead: 60.0,
+            file_overhead: 3.0,
+        };
+        let buckets = split_timings_with_cost(&timings, 2, &cost_model);
+        assert_eq!(
+            buckets,
+            vec![
+                vec![ft("a", 10.0), ft("e", 2.0)],
+                vec![ft("b", 2.0), ft("c", 2.0), ft("d", 2.0)]
+            ]
+        );
+        let bucket_time = |bucket: &[FileTiming]| {
+            cost_model.node_overhead + bucket.iter().map(|t| cost_model.file_time(t)).sum::<f64>()
+        };
+        assert_eq!(bucket_time(&buckets[0]), 78.0);
+        assert_eq!(bucket_time(&buckets[1]), 75.0);
+    }
 }
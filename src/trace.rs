@@ -0,0 +1,226 @@
+use crate::analysis::Analysis;
+use crate::timings::{CostModel, FileTiming};
+use serde_derive::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Trace in the Chrome Trace Event format (JSON object format), see
+/// https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trace {
+    pub trace_events: Vec<TraceEvent>,
+    pub display_time_unit: &'static str,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TraceEvent {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cat: Option<&'static str>,
+    /// 'X' for complete events (slices) and 'M' for metadata
+    pub ph: &'static str,
+    /// Start in microseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ts: Option<u64>,
+    /// Duration in microseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dur: Option<u64>,
+    pub pid: u32,
+    pub tid: u32,
+    #[serde(skip_serializing_if = "Value::is_null")]
+    pub args: Value,
+}
+
+const PID: u32 = 1;
+
+/// Lays out the spec files of every bucket on its own track in the order they run,
+/// starting with the node overhead, with the examples of a file nested under it
+/// when the timing file has them.
+pub fn predicted_trace(
+    analysis: &Analysis,
+    file_timings: &[FileTiming],
+    cost_model: &CostModel,
+) -> Trace {
+    let file_timings = file_timings
+        .iter()
+        .map(|t| (t.file_path.as_str(), t))
+        .collect::<HashMap<_, _>>();
+
+    let mut trace_events = vec![metadata("process_name", 0, "Predicted schedule")];
+    for (index, bucket) in analysis.buckets.iter().enumerate() {
+        let tid = index as u32 + 1;
+        trace_events.push(metadata(
+            "thread_name",
+            tid,
+            &format!("Bucket {} - {:.2}s", tid, bucket.predicted_time),
+        ));
+        trace_events.push(metadata_sort_index(tid));
+        if bucket.files.is_empty() {
+            continue;
+        }
+
+        let mut time = 0.0;
+        if cost_model.node_overhead > 0.0 {
+            trace_events.push(slice(
+                "Node overhead",
+                "overhead",
+                tid,
+                time,
+                cost_model.node_overhead,
+                Value::Null,
+            ));
+            time += cost_model.node_overhead;
+        }
+
+        for (file_path, predicted_time) in &bucket.files {
+            let file_time = match predicted_time {
+                Some(predicted_time) => predicted_time + cost_model.file_overhead,
+                None => cost_model.file_overhead,
+            };
+            trace_events.push(slice(
+                file_path,
+                "spec_file",
+                tid,
+                time,
+                file_time,
+                json!({
+                    "predicted_time": predicted_time,
+                    "status": if predicted_time.is_some() { "estimated" } else { "uncovered" },
+                }),
+            ));
+
+            if let Some(file_timing) = file_timings.get(file_path.as_str()) {
+                // hooks and other time outside of examples run before the examples
+                let mut example_start =
+                    time + cost_model.file_overhead + file_timing.unattributed_time;
+                for example in &file_timing.examples {
+                    trace_events.push(slice(
+                        &example.name,
+                        "example",
+                        tid,
+                        example_start,
+                        example.time,
+                        Value::Null,
+                    ));
+                    example_start += example.time;
+                }
+            }
+            time += file_time;
+        }
+    }
+
+    Trace {
+        trace_events,
+        display_time_unit: "ms",
+    }
+}
+
+fn slice(
+    name: &str,
+    cat: &'static str,
+    tid: u32,
+    start: f64,
+    duration: f64,
+    args: Value,
+) -> TraceEvent {
+    TraceEvent {
+        name: name.to_string(),
+        cat: Some(cat),
+        ph: "X",
+        ts: Some(micros(start)),
+        dur: Some(micros(duration)),
+        pid: PID,
+        tid,
+        args,
+    }
+}
+
+fn metadata(name: &str, tid: u32, value: &str) -> TraceEvent {
+    TraceEvent {
+        name: name.to_string(),
+        cat: None,
+        ph: "M",
+        ts: None,
+        dur: None,
+        pid: PID,
+        tid,
+        args: json!({ "name": value }),
+    }
+}
+
+/// Keeps the tracks in bucket order instead of sorting them by name.
+fn metadata_sort_index(tid: u32) -> TraceEvent {
+    TraceEvent {
+        args: json!({ "sort_index": tid }),
+        ..metadata("thread_sort_index", tid, "")
+    }
+}
+
+fn micros(seconds: f64) -> u64 {
+    (seconds * 1_000_000.0).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timings::ExampleTiming;
+
+    #[test]
+    fn it_lays_out_files_and_nested_examples() {
+        let cost_model = CostModel {
+            node_overhead: 1.0,
+            file_overhead: 0.0,
+        };
+        let file_timings = vec![
+            FileTiming {
+                file_path: "./spec/a_spec.rb".to_string(),
+                total_time: 3.0,
+                unattributed_time: 0.5,
+                examples: vec![
+                    ExampleTiming {
+                        name: "saves".to_string(),
+                        time: 2.0,
+                    },
+                    ExampleTiming {
+                        name: "loads".to_string(),
+                        time: 0.5,
+                    },
+                ],
+            },
+            FileTiming {
+                file_path: "./spec/b_spec.rb".to_string(),
+                total_time: 1.0,
+                ..Default::default()
+            },
+        ];
+        let analysis = Analysis::new(
+            vec![
+                vec![
+                    ("./spec/a_spec.rb".to_string(), Some(3.0)),
+                    ("./spec/b_spec.rb".to_string(), Some(1.0)),
+                ],
+                vec![],
+            ],
+            0.5,
+            &cost_model,
+        );
+
+        let slices = predicted_trace(&analysis, &file_timings, &cost_model)
+            .trace_events
+            .into_iter()
+            .filter(|e| e.ph == "X")
+            .map(|e| (e.name, e.tid, e.ts.unwrap(), e.dur.unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            slices,
+            vec![
+                ("Node overhead".to_string(), 1, 0, 1_000_000),
+                ("./spec/a_spec.rb".to_string(), 1, 1_000_000, 3_000_000),
+                ("saves".to_string(), 1, 1_500_000, 2_000_000),
+                ("loads".to_string(), 1, 3_500_000, 500_000),
+                ("./spec/b_spec.rb".to_string(), 1, 4_000_000, 1_000_000),
+            ]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+mod test_dir;
+use test_dir::TestDir;
+
+fn setup_test() -> TestDir {
+    let test_dir = TestDir::new();
+    test_dir.create_file("spec/models/tests_a_spec.rb", "_");
+    test_dir.create_file("spec/models/admin/tests_b_spec.rb", "_");
+    test_dir.create_file("spec/lib/tests_c_spec.rb", "_");
+
+    test_dir.create_file(
+        "rspec-timings.txt",
+        r###"
+    [
+        {"file_path":"./spec/models/tests_a_spec.rb","total_time":3.0},
+        {"file_path":"./spec/models/admin/tests_b_spec.rb","total_time":5.0},
+        {"file_path":"./spec/lib/tests_c_spec.rb","total_time":2.0}
+    ]
+    "###,
+    );
+
+    test_dir
+}
+
+#[test]
+fn it_rolls_up_by_directory() {
+    let test_dir = setup_test();
+
+    let mut cmd = test_dir.command("analyze");
+    cmd.arg("--rollup");
+    cmd.arg("dir");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+
+    let stdout: String = test_dir.stdout(&mut cmd);
+    assert_eq!(
+        stdout,
+        "[./spec/models - 8.00s] 2 files, 80.0% of 10.00s\n\
+         [./spec/lib - 2.00s] 1 files, 20.0% of 10.00s"
+    );
+}
+
+#[test]
+fn it_rolls_up_by_codeowners() {
+    let test_dir = setup_test();
+    test_dir.create_file(
+        ".github/CODEOWNERS",
+        "/spec/models/ @org/data\n/spec/models/admin/ @org/admin @alice\n",
+    );
+
+    let mut cmd = test_dir.command("analyze");
+    cmd.arg("--rollup");
+    cmd.arg("codeowners");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+
+    let stdout: String = test_dir.stdout(&mut cmd);
+    assert_eq!(
+        stdout,
+        "[@org/admin @alice - 5.00s] 1 files, 50.0% of 10.00s\n\
+         [@org/data - 3.00s] 1 files, 30.0% of 10.00s\n\
+         Unowned: 2.00s, 1 files, 20.0% of 10.00s\n  \
+         ./spec/lib/tests_c_spec.rb:2.00s"
+    );
+}
+
+#[test]
+fn it_outputs_balance_stats_as_json() {
+    let test_dir = setup_test();
+    test_dir.create_file("spec/lib/tests_d_spec.rb", "_");
+
+    let mut cmd = test_dir.command("analyze");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("--format");
+    cmd.arg("json");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+
+    let analysis: serde_json::Value =
+        serde_json::from_str(&test_dir.stdout::<String>(&mut cmd)).expect("analysis is valid JSON");
+    assert_eq!(analysis["stats"]["total_time"], 10.0);
+    assert_eq!(analysis["stats"]["ideal_time"], 5.0);
+    assert_eq!(analysis["stats"]["lower_bound"], 5.0);
+    assert_eq!(analysis["stats"]["makespan"], 5.0);
+    assert_eq!(analysis["stats"]["efficiency"], 1.0);
+    assert_eq!(
+        analysis["stats"]["bottleneck_file"],
+        serde_json::Value::Null
+    );
+    assert_eq!(analysis["uncovered_count"], 1);
+    assert_eq!(
+        analysis["buckets"][1]["files"][2],
+        serde_json::json!(["./spec/lib/tests_d_spec.rb", null])
+    );
+}
+
+#[test]
+fn it_writes_an_html_report() {
+    let test_dir = setup_test();
+
+    let mut cmd = test_dir.command("analyze");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("--html");
+    cmd.arg(test_dir.path("report.html"));
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    test_dir.output(&mut cmd);
+
+    let html =
+        std::fs::read_to_string(test_dir.path("report.html")).expect("report.html is written");
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("Bucket 2 - 5.00s"));
+    assert!(html.contains("./spec/models/admin/tests_b_spec.rb: 5.00s"));
+}
+
+#[test]
+fn it_outputs_markdown() {
+    let test_dir = setup_test();
+
+    let mut cmd = test_dir.command("analyze");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("--format");
+    cmd.arg("markdown");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+
+    let stdout: String = test_dir.stdout(&mut cmd);
+    assert!(stdout.starts_with("| Bucket | Time | Files | Slowest files |\n"));
+    assert!(stdout.contains(
+        "| 2 | 5.00s | 2 | `./spec/models/tests_a_spec.rb` 3.00s<br>`./spec/lib/tests_c_spec.rb` 2.00s |"
+    ));
+    assert!(!stdout.contains("[BUCKET"));
+}
+
+#[test]
+fn it_writes_a_trace_with_examples() {
+    let test_dir = setup_test();
+    test_dir.create_file(
+        "rspec-results.xml",
+        r#"<testcase name="first" file="./spec/models/tests_a_spec.rb" time="1.0"></testcase>
+<testcase name="second" file="./spec/models/tests_a_spec.rb" time="2.0"></testcase>"#,
+    );
+
+    let mut cmd = test_dir.command("parse");
+    cmd.arg("--with-examples");
+    cmd.arg(test_dir.path("rspec-results.xml"));
+    cmd.arg("-o");
+    cmd.arg(test_dir.path("rspec-examples.txt"));
+    test_dir.output(&mut cmd);
+
+    let mut cmd = test_dir.command("analyze");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("--trace");
+    cmd.arg(test_dir.path("trace.json"));
+    cmd.arg(test_dir.path("rspec-examples.txt"));
+    test_dir.output(&mut cmd);
+
+    let trace: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(test_dir.path("trace.json")).expect("trace.json is written"),
+    )
+    .expect("trace is valid JSON");
+    let slices = trace["traceEvents"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|e| e["ph"] == "X")
+        .map(|e| (e["name"].as_str().unwrap(), e["ts"].as_u64().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        slices,
+        vec![
+            ("./spec/models/tests_a_spec.rb", 0),
+            ("first", 0),
+            ("second", 1_000_000),
+            // not covered by the parsed timings, so they take no time
+            ("./spec/lib/tests_c_spec.rb", 0),
+            ("./spec/models/admin/tests_b_spec.rb", 0),
+        ]
+    );
+}
+
+#[test]
+fn it_writes_metrics_in_the_textfile_format() {
+    let test_dir = setup_test();
+
+    let mut cmd = test_dir.command("metrics");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("--top");
+    cmd.arg("2");
+    cmd.arg("-o");
+    cmd.arg(test_dir.path("rspec.prom"));
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    test_dir.output(&mut cmd);
+
+    let metrics =
+        std::fs::read_to_string(test_dir.path("rspec.prom")).expect("rspec.prom is written");
+    assert!(metrics.contains("\nrspec_timing_suite_seconds 10\n"));
+    assert!(metrics.contains("\nrspec_timing_bucket_predicted_seconds{bucket=\"2\"} 5\n"));
+    assert!(metrics.contains("\nrspec_timing_file_age_seconds "));
+    assert!(metrics.contains(
+        "\nrspec_timing_spec_file_seconds{file=\"./spec/models/admin/tests_b_spec.rb\"} 5\n\
+         rspec_timing_spec_file_seconds{file=\"./spec/models/tests_a_spec.rb\"} 3\n# EOF\n"
+    ));
+    assert!(!test_dir.path("rspec.prom.tmp").exists());
+}
+
+#[test]
+fn it_compares_a_range_of_splits() {
+    let test_dir = setup_test();
+
+    let mut cmd = test_dir.command("analyze");
+    cmd.arg("-s");
+    cmd.arg("1..3");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+
+    let stdout: String = test_dir.stdout(&mut cmd);
+    assert_eq!(
+        stdout,
+        "Splits    Makespan  Max/min  Efficiency  Speedup\n     \
+              1      10.00s     1.00      100.0%        -\n     \
+              2       5.00s     1.00      100.0%    2.00x\n     \
+              3       5.00s     2.50       66.7%    1.00x"
+    );
+}
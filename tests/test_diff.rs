@@ -0,0 +1,66 @@
+mod test_dir;
+use test_dir::TestDir;
+
+fn setup_test() -> TestDir {
+    let test_dir = TestDir::new();
+    test_dir.create_file(
+        "old-timings.txt",
+        r###"
+    [
+        {"file_path":"./spec/tests_a_spec.rb","total_time":10.0},
+        {"file_path":"./spec/nested/tests_b_spec.rb","total_time":5.0}
+    ]
+    "###,
+    );
+    test_dir.create_file(
+        "new-timings.txt",
+        r###"
+    [
+        {"file_path":"./spec/tests_a_spec.rb","total_time":13.0},
+        {"file_path":"./spec/nested/tests_b_spec.rb","total_time":5.0},
+        {"file_path":"./spec/nested/tests_c_spec.rb","total_time":1.0}
+    ]
+    "###,
+    );
+
+    test_dir
+}
+
+#[test]
+fn it_diffs_timings_as_json() {
+    let test_dir = setup_test();
+
+    let mut cmd = test_dir.command("diff");
+    cmd.arg("--format");
+    cmd.arg("json");
+    cmd.arg(test_dir.path("old-timings.txt"));
+    cmd.arg(test_dir.path("new-timings.txt"));
+
+    let diff: serde_json::Value =
+        serde_json::from_str(&test_dir.stdout::<String>(&mut cmd)).expect("diff is valid JSON");
+    assert_eq!(diff["delta"], 4.0);
+    assert_eq!(diff["files"][0]["path"], "./spec/tests_a_spec.rb");
+    assert_eq!(diff["files"][1]["kind"], "added");
+    assert_eq!(diff["files"].as_array().map(Vec::len), Some(2));
+}
+
+#[test]
+fn it_fails_if_the_suite_got_slower() {
+    let test_dir = setup_test();
+
+    let mut cmd = test_dir.command("diff");
+    cmd.arg("--fail-if-slower");
+    cmd.arg("30%");
+    cmd.arg(test_dir.path("old-timings.txt"));
+    cmd.arg(test_dir.path("new-timings.txt"));
+    test_dir.output(&mut cmd);
+
+    let mut cmd = test_dir.command("diff");
+    cmd.arg("--fail-if-slower");
+    cmd.arg("20%");
+    cmd.arg(test_dir.path("old-timings.txt"));
+    cmd.arg(test_dir.path("new-timings.txt"));
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("26.7% slower"));
+}
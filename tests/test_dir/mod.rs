@@ -1,3 +1,12 @@
+// Each integration test only uses some of the helpers, and the helpers predate some
+// of the lints of newer clippy versions
+#![allow(dead_code, unused_imports)]
+#![allow(
+    clippy::expect_fun_call,
+    clippy::needless_borrows_for_generic_args,
+    clippy::redundant_static_lifetimes
+)]
+
 use std::env;
 use std::fmt;
 use std::fs::{self, File};
@@ -0,0 +1,65 @@
+mod test_dir;
+use test_dir::TestDir;
+
+fn setup_test() -> TestDir {
+    let test_dir = TestDir::new();
+    test_dir.create_file("spec/tests_a_spec.rb", "_");
+    test_dir.create_file("spec/tests_b_spec.rb", "_");
+    test_dir.create_file("spec/nested/tests_c_spec.rb", "_");
+
+    test_dir.create_file(
+        "rspec-timings.txt",
+        r###"
+    [
+        {"file_path":"./spec/tests_a_spec.rb","total_time":3.3},
+        {"file_path":"./spec/nested/tests_c_spec.rb","total_time":31.903082000000023}
+    ]
+    "###,
+    );
+
+    test_dir
+}
+
+#[test]
+fn jobs_read_their_files_back_from_the_matrix() {
+    let test_dir = setup_test();
+
+    let mut cmd = test_dir.command("matrix");
+    cmd.arg("--provider");
+    cmd.arg("github");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    let matrix: serde_json::Value =
+        serde_json::from_str(&test_dir.stdout::<String>(&mut cmd)).unwrap();
+
+    let include = matrix["include"].as_array().unwrap();
+    assert_eq!(include.len(), 2);
+    assert_eq!(include[0]["index"], 0);
+    assert_eq!(include[0]["predicted_seconds"], 31.903082000000023);
+
+    let mut all_files = vec![];
+    for entry in include {
+        let mut cmd = test_dir.command("split");
+        cmd.arg("--format");
+        cmd.arg("newline");
+        cmd.arg("--from-matrix");
+        cmd.arg(entry.to_string());
+        all_files.extend(
+            test_dir
+                .stdout::<String>(&mut cmd)
+                .lines()
+                .map(|l| l.to_string())
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    assert_eq!(
+        all_files,
+        vec![
+            "./spec/nested/tests_c_spec.rb",
+            "./spec/tests_a_spec.rb",
+            "./spec/tests_b_spec.rb"
+        ]
+    );
+}
@@ -0,0 +1,149 @@
+use std::process;
+
+mod test_dir;
+use test_dir::TestDir;
+
+fn setup_test() -> TestDir {
+    let test_dir = TestDir::new();
+    test_dir.create_file("spec/tests_a_spec.rb", "_");
+    test_dir.create_file("spec/tests_b_spec.rb", "_");
+    test_dir.create_file("spec/nested/tests_c_spec.rb", "_");
+
+    test_dir.create_file(
+        "rspec-timings.txt",
+        r###"
+    [
+        {"file_path":"./spec/tests_a_spec.rb","total_time":3.3},
+        {"file_path":"./spec/nested/tests_c_spec.rb","total_time":31.903082000000023}
+    ]
+    "###,
+    );
+
+    test_dir
+}
+
+fn plan(test_dir: &TestDir) -> String {
+    let mut cmd = test_dir.command("plan");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("-o");
+    cmd.arg(test_dir.path("plan.json"));
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    test_dir.stdout(&mut cmd)
+}
+
+fn split_from_plan(test_dir: &TestDir, current_split: u32, fingerprint: &str) -> process::Command {
+    let mut cmd = test_dir.command("split");
+    cmd.arg("-s");
+    cmd.arg("2");
+
+    cmd.arg("-c");
+    cmd.arg(current_split.to_string());
+
+    cmd.arg("--plan");
+    cmd.arg(test_dir.path("plan.json"));
+    cmd.arg("--expect-fingerprint");
+    cmd.arg(fingerprint);
+
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    cmd
+}
+
+#[test]
+fn it_splits_from_the_plan() {
+    let test_dir = setup_test();
+    let fingerprint = plan(&test_dir);
+
+    assert_eq!(
+        test_dir.stdout::<String>(&mut split_from_plan(&test_dir, 0, &fingerprint)),
+        "./spec/nested/tests_c_spec.rb"
+    );
+    assert_eq!(
+        test_dir.stdout::<String>(&mut split_from_plan(&test_dir, 1, &fingerprint)),
+        "./spec/tests_a_spec.rb ./spec/tests_b_spec.rb"
+    );
+}
+
+#[test]
+fn it_refuses_to_split_when_inputs_differ_from_the_plan() {
+    let test_dir = setup_test();
+    let fingerprint = plan(&test_dir);
+
+    test_dir.create_file("spec/tests_d_spec.rb", "_");
+    let output = split_from_plan(&test_dir, 0, &fingerprint)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let mut cmd = test_dir.command("split");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("-c");
+    cmd.arg("0");
+    cmd.arg("--expect-fingerprint");
+    cmd.arg(&fingerprint);
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    assert!(!cmd.output().unwrap().status.success());
+}
+
+#[test]
+fn it_reports_how_the_run_compared_to_the_plan() {
+    let test_dir = setup_test();
+    plan(&test_dir);
+
+    test_dir.create_file(
+        "node-0/rspec-results.xml",
+        r#"<testcase file="./spec/tests_a_spec.rb" time="5.3"></testcase>
+<testcase file="./spec/tests_b_spec.rb" time="1.0"></testcase>"#,
+    );
+    test_dir.create_file(
+        "node-1/rspec-results.xml",
+        r#"<testcase file="./spec/nested/tests_c_spec.rb" time="30.0"></testcase>"#,
+    );
+
+    let mut cmd = test_dir.command("report");
+    cmd.arg("--format");
+    cmd.arg("json");
+    cmd.arg("--plan");
+    cmd.arg(test_dir.path("plan.json"));
+    cmd.arg(test_dir.path("node-0/rspec-results.xml"));
+    cmd.arg(test_dir.path("node-1/rspec-results.xml"));
+
+    let report: serde_json::Value =
+        serde_json::from_str(&test_dir.stdout::<String>(&mut cmd)).expect("report is valid JSON");
+    assert_eq!(report["nodes"][0]["actual_time"], 30.0);
+    assert_eq!(report["nodes"][1]["actual_time"], 6.3);
+    assert_eq!(report["actual_makespan"], 30.0);
+    assert_eq!(
+        report["deviations"][0]["file_path"],
+        "./spec/tests_a_spec.rb"
+    );
+}
+
+#[test]
+fn it_reports_predictions_without_the_node_overhead() {
+    let test_dir = setup_test();
+    let mut cmd = test_dir.command("plan");
+    cmd.args(["-s", "1", "--node-overhead", "60", "-o"]);
+    cmd.arg(test_dir.path("plan.json"));
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    test_dir.output(&mut cmd);
+
+    test_dir.create_file(
+        "rspec-results.xml",
+        r#"<testcase file="./spec/tests_a_spec.rb" time="3.3"></testcase>
+<testcase file="./spec/nested/tests_c_spec.rb" time="31.7"></testcase>"#,
+    );
+
+    let mut cmd = test_dir.command("report");
+    cmd.args(["--format", "json", "--plan"]);
+    cmd.arg(test_dir.path("plan.json"));
+    cmd.arg(test_dir.path("rspec-results.xml"));
+
+    let report: serde_json::Value =
+        serde_json::from_str(&test_dir.stdout::<String>(&mut cmd)).expect("report is valid JSON");
+    let predicted_time = report["nodes"][0]["predicted_time"].as_f64().unwrap();
+    assert!((predicted_time - 35.2).abs() < 0.01);
+    assert_eq!(report["actual_makespan"], 35.0);
+}
@@ -0,0 +1,205 @@
+use std::fs;
+use std::process;
+
+mod test_dir;
+use test_dir::TestDir;
+
+/// Stands in for 'bundle exec rspec', writing a JUnit file with every file taking
+/// 1.5s and failing with exit code 3.
+static FAKE_RSPEC: &str = r###"
+while [ "$1" != "--out" ]; do shift; done
+out=$2
+shift 2
+echo '<testsuite name="rspec">' > "$out"
+for f in "$@"; do
+    echo "<testcase classname=\"c\" name=\"n\" file=\"$f\" time=\"1.5\"></testcase>" >> "$out"
+done
+echo '</testsuite>' >> "$out"
+touch ran
+exit 3
+"###;
+
+fn setup_test() -> TestDir {
+    let test_dir = TestDir::new();
+    test_dir.create_file("spec/tests_a_spec.rb", "_");
+    test_dir.create_file("spec/tests_b_spec.rb", "_");
+    test_dir.create_file("fake-rspec.sh", FAKE_RSPEC);
+
+    test_dir.create_file(
+        "rspec-timings.txt",
+        r###"
+    [
+        {"file_path":"./spec/tests_a_spec.rb","total_time":3.3},
+        {"file_path":"./spec/tests_b_spec.rb","total_time":1.2}
+    ]
+    "###,
+    );
+
+    test_dir
+}
+
+fn run_with_current_split(test_dir: &TestDir, current_split: u32) -> process::Command {
+    let mut cmd = test_dir.command("run");
+    cmd.arg("-s");
+    cmd.arg("4");
+
+    cmd.arg("-c");
+    cmd.arg(current_split.to_string());
+
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    cmd.arg("--");
+    cmd.arg("sh");
+    cmd.arg("fake-rspec.sh");
+    cmd
+}
+
+#[test]
+fn it_runs_the_split_and_records_timings() {
+    let test_dir = setup_test();
+    let output = run_with_current_split(&test_dir, 0).output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let timings: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_dir.path("rspec-parsed-0.txt")).unwrap())
+            .unwrap();
+    assert_eq!(
+        timings,
+        serde_json::json!([{"file_path": "./spec/tests_a_spec.rb", "total_time": 1.5}])
+    );
+    assert!(test_dir.path("rspec-results-0.xml").exists());
+}
+
+#[test]
+fn it_does_not_parse_junit_output_of_an_earlier_run() {
+    let test_dir = setup_test();
+    test_dir.create_file(
+        "rspec-results-0.xml",
+        r#"<testcase file="./spec/tests_a_spec.rb" time="9.9"></testcase>"#,
+    );
+
+    let mut cmd = test_dir.command("run");
+    cmd.args(["-s", "4", "-c", "0"]);
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    cmd.args(["--", "sh", "-c", "exit 2"]);
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+
+    assert!(!test_dir.path("rspec-results-0.xml").exists());
+    assert!(!test_dir.path("rspec-parsed-0.txt").exists());
+}
+
+#[test]
+fn it_does_not_run_the_entire_suite_for_empty_splits() {
+    let test_dir = setup_test();
+    test_dir.output(&mut run_with_current_split(&test_dir, 2));
+
+    assert!(!test_dir.path("ran").exists());
+    assert_eq!(
+        fs::read_to_string(test_dir.path("rspec-parsed-2.txt")).unwrap(),
+        "[]"
+    );
+}
+
+#[test]
+fn it_runs_every_split_locally_and_updates_timings() {
+    let test_dir = setup_test();
+    let mut cmd = test_dir.command("run-local");
+    cmd.arg("-j");
+    cmd.arg("3");
+    cmd.arg("--in-place");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    cmd.arg("--");
+    cmd.arg("sh");
+    cmd.arg("-c");
+    cmd.arg(r#"echo "env=$TEST_ENV_NUMBER"; sh fake-rspec.sh "$@""#);
+    cmd.arg("fake-rspec");
+
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[1] env=\n"));
+    assert!(stdout.contains("[2] env=2\n"));
+    assert!(stdout.contains("[JOB 1 - predicted 3.30s"));
+    assert!(!stdout.contains("[JOB 3"));
+
+    let timings: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_dir.path("rspec-timings.txt")).unwrap())
+            .unwrap();
+    assert_eq!(
+        timings,
+        serde_json::json!([
+            {"file_path": "./spec/tests_a_spec.rb", "total_time": 1.5},
+            {"file_path": "./spec/tests_b_spec.rb", "total_time": 1.5}
+        ])
+    );
+}
+
+#[test]
+fn it_does_not_parse_local_junit_output_of_an_earlier_run() {
+    let test_dir = setup_test();
+    test_dir.create_file(
+        "rspec-results-local-1.xml",
+        r#"<testcase file="./spec/tests_b_spec.rb" time="9.9"></testcase>"#,
+    );
+
+    let mut cmd = test_dir.command("run-local");
+    cmd.args(["-j", "2", "-o"]);
+    cmd.arg(test_dir.path("rspec-updated.txt"));
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    cmd.args(["--", "sh", "-c"]);
+    cmd.arg(r#"[ "$TEST_ENV_NUMBER" = 2 ] && exit 1; sh fake-rspec.sh "$@""#);
+    cmd.arg("fake-rspec");
+    cmd.output().unwrap();
+
+    let timings: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_dir.path("rspec-updated.txt")).unwrap())
+            .unwrap();
+    assert_eq!(
+        timings,
+        serde_json::json!([
+            {"file_path": "./spec/tests_a_spec.rb", "total_time": 1.5},
+            {"file_path": "./spec/tests_b_spec.rb", "total_time": 1.2}
+        ])
+    );
+    assert!(fs::read_to_string(test_dir.path("rspec-timings.txt"))
+        .unwrap()
+        .contains("3.3"));
+}
+
+#[test]
+fn it_requires_jobs_and_an_output_to_run_locally() {
+    let test_dir = setup_test();
+    let run_local = |args: &[&str]| {
+        let mut cmd = test_dir.command("run-local");
+        cmd.args(args);
+        cmd.arg(test_dir.path("rspec-timings.txt"));
+        cmd.args(["--", "sh", "fake-rspec.sh"]);
+        cmd.output().unwrap()
+    };
+
+    assert!(!run_local(&["-j", "0", "--in-place"]).status.success());
+    assert!(!run_local(&["-j", "2"]).status.success());
+    assert!(!test_dir.path("ran").exists());
+}
+
+#[test]
+fn it_predicts_local_jobs_with_the_overheads() {
+    let test_dir = setup_test();
+    let run_local = |args: &[&str]| {
+        let mut cmd = test_dir.command("run-local");
+        cmd.args(["-j", "1", "-o"]);
+        cmd.arg(test_dir.path("rspec-updated.txt"));
+        cmd.args(args);
+        cmd.arg(test_dir.path("rspec-timings.txt"));
+        cmd.args(["--", "sh", "fake-rspec.sh"]);
+        cmd.output().unwrap()
+    };
+
+    let output = run_local(&["--node-overhead", "2", "--file-overhead", "0.5"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("[JOB 1 - predicted 7.50s"));
+
+    let output = run_local(&["--file-overhead=-1"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("must not be negative"));
+}
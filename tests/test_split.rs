@@ -1,3 +1,11 @@
+// The split tests predate some of the lints of newer clippy versions
+#![allow(
+    clippy::bool_assert_comparison,
+    clippy::needless_borrow,
+    clippy::needless_return
+)]
+
+use std::fs;
 use std::process;
 
 mod test_dir;
@@ -88,3 +96,246 @@ fn it_covers_all_test_files() {
         true
     );
 }
+
+#[test]
+fn it_exits_with_distinct_codes_for_invalid_invocations() {
+    let test_dir = setup_test();
+    let output = split_with_current_split(&test_dir, 4).output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+
+    test_dir.create_file("malformed-timings.txt", "[{\"file_path\":");
+    let mut cmd = test_dir.command("split");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("-c");
+    cmd.arg("0");
+    cmd.arg(test_dir.path("malformed-timings.txt"));
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("Error: malformed timing file"));
+
+    let mut cmd = test_dir.command("split");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("-c");
+    cmd.arg("0");
+    cmd.arg(test_dir.path("missing-timings.txt"));
+    assert_eq!(cmd.output().unwrap().status.code(), Some(6));
+
+    fs::remove_dir_all(test_dir.path("spec")).unwrap();
+    let output = split_with_current_split(&test_dir, 0).output().unwrap();
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn it_guards_against_empty_splits() {
+    let test_dir = setup_test();
+    let split = |args: &[&str]| {
+        let mut cmd = test_dir.command("split");
+        cmd.args(["-s", "6", "-c", "4"]);
+        cmd.args(args);
+        cmd.arg(test_dir.path("rspec-timings.txt"));
+        cmd.output().unwrap()
+    };
+
+    let output = split(&[]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\n");
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("WARNING: split 4 has no spec files"));
+
+    let output = split(&["--on-empty", "sentinel"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "__EMPTY_SPLIT__\n"
+    );
+    let output = split(&["--on-empty", "sentinel", "--empty-sentinel", "none"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "none\n");
+
+    let output = split(&["--on-empty", "noop", "--noop-arg", "spec/noop_spec.rb"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "spec/noop_spec.rb\n"
+    );
+    assert!(!split(&["--on-empty", "noop"]).status.success());
+
+    let output = split(&["--on-empty", "exit"]);
+    assert_eq!(output.status.code(), Some(7));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn it_outputs_one_file_per_line_without_empty_entries() {
+    let test_dir = setup_test();
+    let mut cmd = split_with_current_split(&test_dir, 3);
+    cmd.arg("--format");
+    cmd.arg("newline");
+
+    let stdout = test_dir.stdout::<String>(&mut cmd);
+    let mut files = stdout.lines().collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(
+        files,
+        vec!["./spec/nested/tests_d_spec.rb", "./spec/tests_b_spec.rb"]
+    );
+}
+
+#[test]
+fn it_writes_all_splits_with_a_manifest() {
+    let test_dir = setup_test();
+    let mut cmd = test_dir.command("split");
+    cmd.arg("-s");
+    cmd.arg("4");
+    cmd.arg("--all");
+    cmd.arg("--out-dir");
+    cmd.arg(test_dir.path("buckets"));
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    test_dir.output(&mut cmd);
+
+    for i in 0..4 {
+        let bucket =
+            fs::read_to_string(test_dir.path(&format!("buckets/bucket-{}.txt", i))).unwrap();
+        assert_eq!(
+            bucket.trim_end(),
+            test_dir.stdout::<String>(&mut split_with_current_split(&test_dir, i))
+        );
+    }
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_dir.path("buckets/manifest.json")).unwrap())
+            .unwrap();
+    assert_eq!(manifest["total_splits"], 4);
+    assert_eq!(manifest["buckets"].as_array().unwrap().len(), 4);
+    assert_eq!(manifest["buckets"][3]["file"], "bucket-3.txt");
+    assert_eq!(manifest["buckets"][3]["uncovered_count"], 2);
+    assert!(manifest["fingerprint"].is_string());
+}
+
+#[test]
+fn it_reads_splits_from_the_ci_environment() {
+    let test_dir = setup_test();
+    let mut cmd = test_dir.command("split");
+    cmd.arg("--ci");
+    cmd.arg("gitlab");
+    cmd.arg("--verbose");
+    cmd.env("CI_NODE_INDEX", "1");
+    cmd.env("CI_NODE_TOTAL", "4");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+
+    let output = test_dir.output(&mut cmd);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim_end(),
+        test_dir.stdout::<String>(&mut split_with_current_split(&test_dir, 0))
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Detected GitLab CI"));
+}
+
+#[test]
+fn it_outputs_one_line_per_process() {
+    let test_dir = setup_test();
+    let mut cmd = test_dir.command("split");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("-c");
+    cmd.arg("1");
+    cmd.arg("--processes-per-node");
+    cmd.arg("2");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+
+    assert_eq!(
+        test_dir.stdout::<String>(&mut cmd),
+        "./spec/tests_a_spec.rb\n./spec/nested/tests_d_spec.rb ./spec/tests_b_spec.rb"
+    );
+}
+
+#[test]
+fn it_rejects_nodes_without_processes() {
+    let test_dir = setup_test();
+    let mut cmd = test_dir.command("split");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("-c");
+    cmd.arg("0");
+    cmd.arg("--processes-per-node");
+    cmd.arg("0");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn it_rejects_zero_splits() {
+    let test_dir = setup_test();
+    let timing_file = test_dir.path("rspec-timings.txt");
+    let timing_file = timing_file.to_str().unwrap();
+    let plan_file = test_dir.path("plan.json");
+    let out_dir = test_dir.path("buckets");
+    for args in &[
+        vec!["split", "-s", "0", "-c", "0", timing_file],
+        vec![
+            "split",
+            "-s",
+            "0",
+            "--all",
+            "--out-dir",
+            out_dir.to_str().unwrap(),
+            timing_file,
+        ],
+        vec![
+            "plan",
+            "-s",
+            "0",
+            "-o",
+            plan_file.to_str().unwrap(),
+            timing_file,
+        ],
+        vec!["matrix", "-s", "0", timing_file],
+        vec!["metrics", "-s", "0", timing_file],
+    ] {
+        let mut cmd = test_dir.command(args[0]);
+        cmd.args(&args[1..]);
+        let output = cmd.output().unwrap();
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(output.stdout.is_empty(), "{:?}", args);
+    }
+    assert!(!plan_file.exists());
+    assert!(!out_dir.exists());
+}
+
+#[test]
+fn it_guards_against_empty_splits_when_writing_all_splits() {
+    let test_dir = setup_test();
+    let split_all = |args: &[&str]| {
+        let mut cmd = test_dir.command("split");
+        cmd.args(["-s", "6", "--all", "--out-dir"]);
+        cmd.arg(test_dir.path("buckets"));
+        cmd.args(args);
+        cmd.arg(test_dir.path("rspec-timings.txt"));
+        cmd.output().unwrap()
+    };
+
+    let output = split_all(&["--on-empty", "exit"]);
+    assert_eq!(output.status.code(), Some(7));
+    assert!(!test_dir.path("buckets").exists());
+
+    let output = split_all(&["--on-empty", "sentinel"]);
+    assert!(output.status.success());
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(test_dir.path("buckets/manifest.json")).unwrap())
+            .unwrap();
+    let buckets = manifest["buckets"].as_array().unwrap();
+    assert_eq!(buckets.iter().filter(|b| b["empty"] == true).count(), 3);
+    for bucket in buckets {
+        let contents = fs::read_to_string(
+            test_dir.path(&format!("buckets/{}", bucket["file"].as_str().unwrap())),
+        )
+        .unwrap();
+        assert_eq!(bucket["empty"] == true, contents == "__EMPTY_SPLIT__\n");
+    }
+}
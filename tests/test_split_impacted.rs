@@ -0,0 +1,159 @@
+use std::process;
+
+mod test_dir;
+use test_dir::TestDir;
+
+fn setup_test() -> TestDir {
+    let test_dir = TestDir::new();
+    test_dir.create_file("spec/tests_a_spec.rb", "_");
+    test_dir.create_file("spec/tests_b_spec.rb", "_");
+    test_dir.create_file("spec/nested/tests_c_spec.rb", "_");
+    test_dir.create_file("spec/nested/tests_d_spec.rb", "_");
+
+    test_dir.create_file(
+        "rspec-timings.txt",
+        r###"
+    [
+        {"file_path":"./spec/tests_a_spec.rb","total_time":3.3},
+        {"file_path":"./spec/tests_b_spec.rb","total_time":1.2},
+        {"file_path":"./spec/nested/tests_c_spec.rb","total_time":31.903082000000023}
+    ]
+    "###,
+    );
+
+    test_dir.create_file(
+        "coverage/.resultset.json",
+        r###"
+    {
+        "/code/spec/tests_a_spec.rb": {
+            "coverage": {"/code/app/models/a.rb": {"lines": [null, 1]}},
+            "timestamp": 1546300800
+        },
+        "/code/spec/nested/tests_c_spec.rb": {
+            "coverage": {
+                "/code/app/models/a.rb": {"lines": [null, 1]},
+                "/code/app/models/c.rb": {"lines": [null, 4]}
+            },
+            "timestamp": 1546300800
+        }
+    }
+    "###,
+    );
+
+    test_dir.create_file(
+        "changed-files.txt",
+        "app/models/a.rb\nspec/nested/tests_d_spec.rb\n",
+    );
+
+    let mut cmd = test_dir.command("index-coverage");
+    cmd.arg("-t");
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    cmd.arg("--root");
+    cmd.arg("/code");
+    cmd.arg(test_dir.path("coverage/.resultset.json"));
+    test_dir.output(&mut cmd);
+
+    test_dir
+}
+
+fn split_impacted_with_current_split(test_dir: &TestDir, current_split: u32) -> process::Command {
+    let mut cmd = test_dir.command("split-impacted");
+    cmd.arg("-s");
+    cmd.arg("2");
+
+    cmd.arg("-c");
+    cmd.arg(current_split.to_string());
+
+    cmd.arg("--changed-files");
+    cmd.arg(test_dir.path("changed-files.txt"));
+
+    cmd.arg(test_dir.path("rspec-timings.txt"));
+    cmd
+}
+
+#[test]
+fn it_only_outputs_impacted_specs() {
+    let test_dir = setup_test();
+    assert!(test_dir.path("rspec-timings.coverage-index.json").exists());
+
+    let mut all_splits = vec![];
+    for i in 0..2 {
+        all_splits.extend(
+            test_dir
+                .stdout::<String>(&mut split_impacted_with_current_split(&test_dir, i))
+                .split(' ')
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_owned())
+                .collect::<Vec<_>>(),
+        );
+    }
+    all_splits.sort();
+
+    assert_eq!(
+        all_splits,
+        vec![
+            "./spec/nested/tests_c_spec.rb",
+            "./spec/nested/tests_d_spec.rb",
+            "./spec/tests_a_spec.rb",
+        ]
+    );
+}
+
+#[test]
+fn it_does_not_run_the_entire_suite_without_impacted_specs() {
+    let test_dir = setup_test();
+    test_dir.create_file("changed-files.txt", "README.md\n");
+
+    let mut cmd = split_impacted_with_current_split(&test_dir, 0);
+    cmd.arg("--ignore-unindexed");
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(7));
+    assert!(output.stdout.is_empty());
+
+    let mut cmd = split_impacted_with_current_split(&test_dir, 1);
+    cmd.arg("--ignore-unindexed");
+    cmd.arg("--on-empty");
+    cmd.arg("sentinel");
+    assert_eq!(test_dir.stdout::<String>(&mut cmd), "__EMPTY_SPLIT__");
+}
+
+#[test]
+fn it_outputs_the_full_split_for_changed_files_missing_from_the_index() {
+    let test_dir = setup_test();
+    test_dir.create_file(
+        "changed-files.txt",
+        "spec/spec_helper.rb\napp/new_model.rb\nconfig/routes.rb\n",
+    );
+
+    let mut all_splits = vec![];
+    for i in 0..2 {
+        let output = split_impacted_with_current_split(&test_dir, i)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("WARNING: ./spec/spec_helper.rb isn't in the coverage index"));
+        assert!(stderr.contains("WARNING: ./config/routes.rb isn't in the coverage index"));
+        all_splits.extend(
+            String::from_utf8(output.stdout)
+                .unwrap()
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>(),
+        );
+    }
+    all_splits.sort();
+    assert_eq!(
+        all_splits,
+        vec![
+            "./spec/nested/tests_c_spec.rb",
+            "./spec/nested/tests_d_spec.rb",
+            "./spec/tests_a_spec.rb",
+            "./spec/tests_b_spec.rb",
+        ]
+    );
+
+    let mut cmd = split_impacted_with_current_split(&test_dir, 0);
+    cmd.arg("--ignore-unindexed");
+    assert_eq!(cmd.output().unwrap().status.code(), Some(7));
+}
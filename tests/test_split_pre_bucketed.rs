@@ -1,3 +1,10 @@
+// The split tests predate some of the lints of newer clippy versions
+#![allow(
+    clippy::bool_assert_comparison,
+    clippy::needless_borrow,
+    clippy::needless_return
+)]
+
 use std::process;
 
 mod test_dir;
@@ -33,7 +40,7 @@ fn setup_test() -> TestDir {
 fn split_with_current_split(test_dir: &TestDir, current_split: u32) -> process::Command {
     let mut cmd = test_dir.command("split-pre-bucketed");
     cmd.arg("-s");
-    cmd.arg("4");
+    cmd.arg("2");
 
     cmd.arg("-c");
     cmd.arg(current_split.to_string());
@@ -44,7 +51,7 @@ fn split_with_current_split(test_dir: &TestDir, current_split: u32) -> process::
 
 fn all_splits(test_dir: &TestDir) -> Vec<Vec<String>> {
     let mut all_splits = vec![];
-    for i in 0..4 {
+    for i in 0..2 {
         all_splits.push(
             test_dir
                 .stdout::<String>(&mut split_with_current_split(&test_dir, i))
@@ -87,3 +94,208 @@ fn it_covers_all_test_files() {
         true
     );
 }
+
+#[test]
+fn it_fails_for_splits_not_in_the_file() {
+    let test_dir = setup_test();
+    let output = split_with_current_split(&test_dir, 2).output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Error: current split should be between [0..2), got 2\n\
+         hint: --current-split is 0-based, the last of 2 splits is 1\n"
+    );
+}
+
+#[test]
+fn it_rebalances_new_and_deleted_files() {
+    let test_dir = setup_test();
+    test_dir.create_file(
+        "pre-bucketed.json",
+        r###"
+[
+    [
+        ["./spec/tests_a_spec.rb", 524],
+        ["./spec/deleted_spec.rb", 3]
+    ],
+    [
+        ["./spec/nested/tests_c_spec.rb", 2.4213],
+        ["./spec/nested/tests_e_spec.rb", 5]
+    ]
+]
+    "###,
+    );
+    test_dir.create_file(
+        "timings.json",
+        r###"
+[
+    {"file_path":"./spec/tests_a_spec.rb","total_time":500},
+    {"file_path":"./spec/tests_b_spec.rb","total_time":10},
+    {"file_path":"./spec/nested/tests_d_spec.rb","total_time":1}
+]
+    "###,
+    );
+
+    let mut cmd = test_dir.command("rebalance");
+    cmd.arg(test_dir.path("pre-bucketed.json"));
+    cmd.arg(test_dir.path("timings.json"));
+    assert_eq!(
+        test_dir.stdout::<String>(&mut cmd),
+        "[BUCKET 1 - 527.00s -> 500.00s] 0 added, 1 removed\n\
+         [BUCKET 2 - 7.42s -> 18.42s] 2 added, 0 removed"
+    );
+
+    let rebalanced_file = std::fs::read_to_string(test_dir.path("pre-bucketed.json")).unwrap();
+    assert!(rebalanced_file.starts_with("[\n  [\n    [\n"));
+    assert!(rebalanced_file.ends_with("]\n"));
+    let rebalanced: Vec<Vec<(String, Option<f64>)>> =
+        serde_json::from_str(&rebalanced_file).expect("pre-bucketed file is rewritten");
+    assert_eq!(
+        rebalanced,
+        vec![
+            vec![("./spec/tests_a_spec.rb".to_string(), Some(500.0))],
+            vec![
+                ("./spec/nested/tests_c_spec.rb".to_string(), Some(2.4213)),
+                ("./spec/nested/tests_e_spec.rb".to_string(), Some(5.0)),
+                ("./spec/tests_b_spec.rb".to_string(), Some(10.0)),
+                ("./spec/nested/tests_d_spec.rb".to_string(), Some(1.0)),
+            ]
+        ]
+    );
+}
+
+#[test]
+fn it_lints_the_pre_bucketed_file() {
+    let test_dir = setup_test();
+    test_dir.create_file(
+        "pre-bucketed.json",
+        r###"
+[
+    [
+        ["./spec/tests_a_spec.rb", 524],
+        ["./spec/deleted_spec.rb", 3]
+    ],
+    [
+        ["./spec/tests_a_spec.rb", 524],
+        ["./spec/tests_b_spec.rb", 1]
+    ]
+]
+    "###,
+    );
+
+    let mut cmd = test_dir.command("lint");
+    cmd.arg("-s");
+    cmd.arg("3");
+    cmd.arg(test_dir.path("pre-bucketed.json"));
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "expected 3 splits (--total-splits) but the file has 2\n\
+         ./spec/deleted_spec.rb in split 0 doesn't exist\n\
+         ./spec/tests_a_spec.rb is in splits 0, 1 and would run 2 times\n\
+         ./spec/nested/tests_c_spec.rb isn't in any split and would be added to the last split\n\
+         ./spec/nested/tests_d_spec.rb isn't in any split and would be added to the last split\n\
+         ./spec/nested/tests_e_spec.rb isn't in any split and would be added to the last split"
+    );
+
+    let mut cmd = test_dir.command("split-pre-bucketed");
+    cmd.arg("--strict");
+    cmd.arg("-s");
+    cmd.arg("2");
+    cmd.arg("-c");
+    cmd.arg("0");
+    cmd.arg(test_dir.path("pre-bucketed.json"));
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("./spec/deleted_spec.rb in split 0 doesn't exist"));
+
+    let mut cmd = test_dir.command("split-pre-bucketed");
+    cmd.args(["-s", "2", "-c", "0"]);
+    cmd.arg(test_dir.path("pre-bucketed.json"));
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "./spec/tests_a_spec.rb ./spec/deleted_spec.rb\n"
+    );
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("WARNING: ./spec/deleted_spec.rb in split 0 doesn't exist"));
+
+    let mut cmd = split_with_current_split(&test_dir, 0);
+    cmd.arg("--strict");
+    assert!(!cmd.output().unwrap().status.success());
+}
+
+#[test]
+fn it_splits_globs_directories_and_overflow_buckets() {
+    let test_dir = setup_test();
+    test_dir.create_file(
+        "pre-bucketed.json",
+        r###"
+[
+    ["spec/nested/**"],
+    {"files": [["./spec/tests_a_spec.rb", 524]], "overflow": true}
+]
+    "###,
+    );
+
+    let split = |current_split: u32| {
+        let mut cmd = test_dir.command("split-pre-bucketed");
+        cmd.arg("--strict");
+        cmd.arg("-s");
+        cmd.arg("2");
+        cmd.arg("-c");
+        cmd.arg(current_split.to_string());
+        cmd.arg(test_dir.path("pre-bucketed.json"));
+        test_dir.stdout::<String>(&mut cmd)
+    };
+    assert_eq!(
+        split(0),
+        "./spec/nested/tests_c_spec.rb ./spec/nested/tests_d_spec.rb ./spec/nested/tests_e_spec.rb"
+    );
+    assert_eq!(split(1), "./spec/tests_a_spec.rb ./spec/tests_b_spec.rb");
+}
+
+#[test]
+fn it_does_not_run_the_entire_suite_for_empty_buckets() {
+    let test_dir = setup_test();
+    test_dir.create_file(
+        "pre-bucketed.json",
+        r###"
+[
+    ["spec/**"],
+    []
+]
+    "###,
+    );
+
+    let split = |args: &[&str]| {
+        let mut cmd = test_dir.command("split-pre-bucketed");
+        cmd.args(["-s", "2", "-c", "1"]);
+        cmd.args(args);
+        cmd.arg(test_dir.path("pre-bucketed.json"));
+        cmd.output().unwrap()
+    };
+
+    let output = split(&[]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\n");
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("WARNING: split 1 has no spec files"));
+
+    let output = split(&["--on-empty", "sentinel"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "__EMPTY_SPLIT__\n"
+    );
+
+    let output = split(&["--on-empty", "exit"]);
+    assert_eq!(output.status.code(), Some(7));
+    assert!(output.stdout.is_empty());
+}